@@ -0,0 +1,7 @@
+-- 删除VIP等级策略表
+DROP TABLE IF EXISTS vip_level_policies;
+
+-- 恢复单用户单会话约束（仅保留每个用户最新的会话）
+DELETE FROM online_users a USING online_users b
+    WHERE a.user_id = b.user_id AND a.id < b.id;
+ALTER TABLE online_users ADD CONSTRAINT online_users_user_id_key UNIQUE (user_id);
@@ -0,0 +1,20 @@
+-- 允许同一用户同时存在多个在线会话
+ALTER TABLE online_users DROP CONSTRAINT IF EXISTS online_users_user_id_key;
+
+-- 创建VIP等级策略表，按VIP等级配置多设备会话策略
+-- session_limit_action: kick_oldest（踢掉最早的会话）或 reject（拒绝新登录）
+CREATE TABLE vip_level_policies (
+    vip_level INTEGER PRIMARY KEY,
+    max_sessions INTEGER NOT NULL DEFAULT 1,
+    session_limit_action VARCHAR(20) NOT NULL DEFAULT 'kick_oldest',
+    allow_shared_hardware BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    CONSTRAINT chk_vip_level_policies_action CHECK (session_limit_action IN ('kick_oldest', 'reject'))
+);
+
+-- 默认策略：免费用户单设备，付费等级逐级放宽
+INSERT INTO vip_level_policies (vip_level, max_sessions, session_limit_action, allow_shared_hardware) VALUES
+    (0, 1, 'kick_oldest', FALSE),
+    (1, 2, 'kick_oldest', FALSE),
+    (2, 3, 'kick_oldest', FALSE);
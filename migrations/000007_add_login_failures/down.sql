@@ -0,0 +1,11 @@
+-- 删除登录锁定表
+DROP TABLE IF EXISTS login_lockouts;
+
+-- 恢复登录日志表结构（失败记录没有对应用户，一并删除）
+DROP INDEX IF EXISTS idx_login_logs_ip_address;
+DROP INDEX IF EXISTS idx_login_logs_username;
+DROP INDEX IF EXISTS idx_login_logs_user_id;
+DELETE FROM login_logs WHERE user_id IS NULL;
+ALTER TABLE login_logs DROP COLUMN IF EXISTS failure_reason;
+ALTER TABLE login_logs DROP COLUMN IF EXISTS username;
+ALTER TABLE login_logs ALTER COLUMN user_id SET NOT NULL;
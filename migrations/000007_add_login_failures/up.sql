@@ -0,0 +1,24 @@
+-- 登录日志同时记录失败的登录尝试：未知用户没有user_id，因此改为可空并记录用户名
+ALTER TABLE login_logs ALTER COLUMN user_id DROP NOT NULL;
+ALTER TABLE login_logs ADD COLUMN username VARCHAR(255) NOT NULL DEFAULT '';
+ALTER TABLE login_logs ADD COLUMN failure_reason VARCHAR(50);
+
+UPDATE login_logs SET username = users.username FROM users WHERE users.id = login_logs.user_id;
+
+CREATE INDEX idx_login_logs_user_id ON login_logs(user_id);
+CREATE INDEX idx_login_logs_username ON login_logs(username);
+CREATE INDEX idx_login_logs_ip_address ON login_logs(ip_address);
+
+-- 创建登录锁定表，按用户名和IP分别统计失败次数
+-- scope: username 或 ip
+CREATE TABLE login_lockouts (
+    id SERIAL PRIMARY KEY,
+    scope VARCHAR(20) NOT NULL,
+    scope_key VARCHAR(255) NOT NULL,
+    failure_count INTEGER NOT NULL DEFAULT 0,
+    window_started_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    lockout_count INTEGER NOT NULL DEFAULT 0,
+    locked_until TIMESTAMP WITH TIME ZONE,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    CONSTRAINT uq_login_lockouts_scope_key UNIQUE (scope, scope_key)
+);
@@ -0,0 +1,3 @@
+-- 删除两步验证相关表
+DROP TABLE IF EXISTS totp_backup_codes;
+DROP TABLE IF EXISTS user_totp;
@@ -0,0 +1,2 @@
+-- 删除离线激活记录表
+DROP TABLE IF EXISTS offline_activations;
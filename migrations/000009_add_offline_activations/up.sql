@@ -0,0 +1,18 @@
+-- 创建离线激活记录表，每个请求码（按nonce区分）只会消耗一次VIP时长或卡密
+-- source: card（消耗充值卡密）或 vip_time（消耗用户VIP时长）
+CREATE TABLE offline_activations (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER REFERENCES users(id),
+    software_id INTEGER NOT NULL REFERENCES software(id),
+    hardware_code VARCHAR(255) NOT NULL,
+    request_nonce VARCHAR(64) UNIQUE NOT NULL,
+    source VARCHAR(20) NOT NULL,
+    card_code VARCHAR(255),
+    vip_level INTEGER NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+-- 创建索引，提高查询效率
+CREATE INDEX idx_offline_activations_user_id ON offline_activations(user_id);
+CREATE INDEX idx_offline_activations_hardware_code ON offline_activations(hardware_code);
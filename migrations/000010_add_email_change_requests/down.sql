@@ -0,0 +1,2 @@
+-- 删除邮箱变更请求表
+DROP TABLE IF EXISTS email_change_requests;
@@ -0,0 +1,17 @@
+-- 创建邮箱变更请求表
+-- 验证码发送到新邮箱，确认后才更新 users.email；旧邮箱收到带取消链接的通知，取消令牌以SHA-256哈希存储
+CREATE TABLE email_change_requests (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    old_email VARCHAR(255) NOT NULL,
+    new_email VARCHAR(255) NOT NULL,
+    code VARCHAR(6) NOT NULL,
+    cancel_token_hash VARCHAR(64) UNIQUE NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    confirmed_at TIMESTAMP WITH TIME ZONE,
+    cancelled_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+-- 创建索引，提高查询效率
+CREATE INDEX idx_email_change_requests_user_id ON email_change_requests(user_id);
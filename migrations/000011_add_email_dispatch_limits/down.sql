@@ -0,0 +1,3 @@
+-- 删除邮件发送记录表和验证码失败次数字段
+DROP TABLE IF EXISTS email_dispatch_logs;
+ALTER TABLE verification_codes DROP COLUMN IF EXISTS failed_attempts;
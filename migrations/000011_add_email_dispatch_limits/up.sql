@@ -0,0 +1,14 @@
+-- 记录验证码校验失败次数，达到上限后验证码作废，防止暴力破解
+ALTER TABLE verification_codes ADD COLUMN failed_attempts INTEGER NOT NULL DEFAULT 0;
+
+-- 创建邮件发送记录表，用于限制重新发送的冷却时间和每日发送次数
+CREATE TABLE email_dispatch_logs (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    purpose VARCHAR(32) NOT NULL,
+    email VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+-- 创建索引，提高查询效率
+CREATE INDEX idx_email_dispatch_logs_user_purpose ON email_dispatch_logs(user_id, purpose, created_at);
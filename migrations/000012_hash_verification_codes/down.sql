@@ -0,0 +1,5 @@
+-- 删除验证码用途字段，哈希后的验证码无法还原，全部作废
+DROP INDEX IF EXISTS idx_verification_codes_user_purpose;
+ALTER TABLE verification_codes DROP COLUMN IF EXISTS purpose;
+UPDATE verification_codes SET used = TRUE, code = '' WHERE LENGTH(code) > 6;
+ALTER TABLE verification_codes ALTER COLUMN code TYPE VARCHAR(6);
@@ -0,0 +1,11 @@
+-- 验证码改为以HMAC-SHA256哈希存储，重置令牌以SHA-256哈希存储
+ALTER TABLE verification_codes ALTER COLUMN code TYPE VARCHAR(64);
+
+-- 区分邮箱验证码和密码重置验证码，避免两种验证码混用
+ALTER TABLE verification_codes ADD COLUMN purpose VARCHAR(32) NOT NULL DEFAULT 'email_verification';
+
+-- 作废所有未使用的明文验证码，用户需要重新获取
+UPDATE verification_codes SET used = TRUE WHERE used = FALSE;
+
+-- 创建索引，提高查询效率
+CREATE INDEX idx_verification_codes_user_purpose ON verification_codes(user_id, purpose);
@@ -0,0 +1,14 @@
+-- 创建免密登录链接表，令牌以SHA-256哈希存储，只能在发起请求的设备上使用一次
+CREATE TABLE magic_link_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    token_hash VARCHAR(64) UNIQUE NOT NULL,
+    hardware_code VARCHAR(255) NOT NULL,
+    ip_address VARCHAR(50) NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+-- 创建索引，提高查询效率
+CREATE INDEX idx_magic_link_tokens_user_id ON magic_link_tokens(user_id);
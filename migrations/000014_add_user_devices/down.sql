@@ -0,0 +1,8 @@
+-- 删除VIP等级策略中的设备绑定配置
+ALTER TABLE vip_level_policies
+    DROP COLUMN IF EXISTS max_devices,
+    DROP COLUMN IF EXISTS unbind_cooldown_hours,
+    DROP COLUMN IF EXISTS unbind_penalty_hours;
+
+-- 删除用户绑定设备表
+DROP TABLE IF EXISTS user_devices;
@@ -0,0 +1,25 @@
+-- 创建用户绑定设备表，unbound_at 不为空表示已解绑，重新绑定时复用原记录
+CREATE TABLE user_devices (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    hardware_code VARCHAR(255) NOT NULL,
+    bound_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_login_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    unbound_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    CONSTRAINT uq_user_devices_user_hardware UNIQUE (user_id, hardware_code)
+);
+
+-- VIP等级策略增加设备绑定配置
+-- max_devices: 最多绑定设备数（0表示不限制）
+-- unbind_cooldown_hours: 两次自助解绑之间的冷却时间（小时）
+-- unbind_penalty_hours: 每次自助解绑扣除的VIP时长（小时，0表示不扣除）
+ALTER TABLE vip_level_policies
+    ADD COLUMN max_devices INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN unbind_cooldown_hours INTEGER NOT NULL DEFAULT 24,
+    ADD COLUMN unbind_penalty_hours INTEGER NOT NULL DEFAULT 0;
+
+-- 默认策略：免费用户绑定2台设备，付费等级逐级放宽
+UPDATE vip_level_policies SET max_devices = 2 WHERE vip_level = 0;
+UPDATE vip_level_policies SET max_devices = 3 WHERE vip_level = 1;
+UPDATE vip_level_policies SET max_devices = 5 WHERE vip_level = 2;
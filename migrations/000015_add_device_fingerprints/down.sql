@@ -0,0 +1,9 @@
+-- 删除硬件指纹相关表
+DROP TABLE IF EXISTS device_fingerprint_changes;
+DROP TABLE IF EXISTS device_fingerprint_components;
+
+-- 恢复用户与硬件码唯一约束（仅保留每个硬件码最新的记录）
+DROP INDEX IF EXISTS uq_user_devices_active_hardware;
+DELETE FROM user_devices a USING user_devices b
+    WHERE a.user_id = b.user_id AND a.hardware_code = b.hardware_code AND a.id < b.id;
+ALTER TABLE user_devices ADD CONSTRAINT uq_user_devices_user_hardware UNIQUE (user_id, hardware_code);
@@ -0,0 +1,29 @@
+-- 绑定设备改为只要求未解绑的记录唯一，重新绑定时新建记录以保留解绑历史
+ALTER TABLE user_devices DROP CONSTRAINT IF EXISTS uq_user_devices_user_hardware;
+CREATE UNIQUE INDEX uq_user_devices_active_hardware ON user_devices(user_id, hardware_code) WHERE unbound_at IS NULL;
+
+-- 创建设备硬件指纹组件表，每台绑定设备的每个组件（cpu、board、disk、mac等）保存最近一次上报的值
+CREATE TABLE device_fingerprint_components (
+    id SERIAL PRIMARY KEY,
+    device_id INTEGER NOT NULL REFERENCES user_devices(id) ON DELETE CASCADE,
+    component VARCHAR(50) NOT NULL,
+    value VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    CONSTRAINT uq_device_fingerprint_components UNIQUE (device_id, component)
+);
+
+-- 创建硬件指纹变更审计表
+-- source: login（登录时）或 heartbeat（心跳时）
+CREATE TABLE device_fingerprint_changes (
+    id SERIAL PRIMARY KEY,
+    device_id INTEGER NOT NULL REFERENCES user_devices(id) ON DELETE CASCADE,
+    component VARCHAR(50) NOT NULL,
+    old_value VARCHAR(255),
+    new_value VARCHAR(255) NOT NULL,
+    source VARCHAR(20) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+-- 创建索引，提高查询效率
+CREATE INDEX idx_device_fingerprint_changes_device_id ON device_fingerprint_changes(device_id);
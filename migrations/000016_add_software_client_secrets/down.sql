@@ -0,0 +1,2 @@
+-- 删除软件客户端密钥表
+DROP TABLE IF EXISTS software_client_secrets;
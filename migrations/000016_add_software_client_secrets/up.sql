@@ -0,0 +1,8 @@
+-- 创建软件客户端密钥表，用于校验客户端请求签名（HMAC-SHA256）
+-- 单独建表，避免密钥随软件列表接口返回给客户端
+CREATE TABLE software_client_secrets (
+    software_id INTEGER PRIMARY KEY REFERENCES software(id) ON DELETE CASCADE,
+    client_secret VARCHAR(128) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
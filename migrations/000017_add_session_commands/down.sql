@@ -0,0 +1,2 @@
+-- 删除会话指令队列表
+DROP TABLE IF EXISTS session_commands;
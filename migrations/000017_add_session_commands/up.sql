@@ -0,0 +1,20 @@
+-- 创建会话指令队列表，管理员或其他服务写入，客户端通过心跳领取并在下一次心跳时确认
+-- 面向用户的指令在写入时按该用户当前的每个在线会话分别生成一条
+-- command_type: force_logout（强制下线）、show_message（显示消息）、update_available（有可用更新）、entitlement_changed（权益变更）
+-- payload: 附加数据（JSON字符串）
+CREATE TABLE session_commands (
+    id SERIAL PRIMARY KEY,
+    session_id INTEGER NOT NULL REFERENCES online_users(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    command_type VARCHAR(30) NOT NULL,
+    message TEXT,
+    payload TEXT,
+    expires_at TIMESTAMP WITH TIME ZONE,
+    delivered_at TIMESTAMP WITH TIME ZONE,
+    acknowledged_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    CONSTRAINT chk_session_commands_type CHECK (command_type IN ('force_logout', 'show_message', 'update_available', 'entitlement_changed'))
+);
+
+-- 创建索引，提高心跳时查询待处理指令的效率
+CREATE INDEX idx_session_commands_pending ON session_commands(session_id) WHERE acknowledged_at IS NULL;
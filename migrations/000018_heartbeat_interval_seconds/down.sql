@@ -0,0 +1,4 @@
+-- 恢复以分钟为单位的会话上报间隔
+DROP INDEX IF EXISTS idx_online_users_activity_interval;
+ALTER TABLE online_users ALTER COLUMN status_interval SET DEFAULT 10;
+UPDATE online_users SET status_interval = GREATEST(status_interval / 60, 1);
@@ -0,0 +1,6 @@
+-- 会话上报间隔改为以秒为单位，已有会话按原来的分钟数换算
+UPDATE online_users SET status_interval = status_interval * 60;
+ALTER TABLE online_users ALTER COLUMN status_interval SET DEFAULT 600;
+
+-- 按会话超时清理时需要扫描最后活动时间和上报间隔
+CREATE INDEX IF NOT EXISTS idx_online_users_activity_interval ON online_users(last_activity_at, status_interval);
@@ -0,0 +1,2 @@
+-- 删除会话历史表
+DROP TABLE IF EXISTS session_history;
@@ -0,0 +1,20 @@
+-- 创建会话历史表，在线会话结束时写入一条记录
+CREATE TABLE session_history (
+    id SERIAL PRIMARY KEY,
+    -- 原 online_users 记录的ID，会话结束后该记录已删除，因此不设外键
+    session_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    hardware_code VARCHAR(255) NOT NULL,
+    software_version VARCHAR(50) NOT NULL,
+    ip_address VARCHAR(50) NOT NULL,
+    started_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    last_activity_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    -- 超时结束的会话以最后活动时间作为结束时间
+    ended_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    end_reason VARCHAR(20) NOT NULL CHECK (end_reason IN ('logout', 'kicked', 'timeout', 'banned')),
+    duration_seconds BIGINT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_session_history_user_id_started_at ON session_history(user_id, started_at DESC);
+CREATE INDEX idx_session_history_ended_at ON session_history(ended_at);
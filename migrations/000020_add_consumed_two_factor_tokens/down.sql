@@ -0,0 +1,2 @@
+-- 删除已使用的两步验证待完成令牌表
+DROP TABLE IF EXISTS consumed_two_factor_tokens;
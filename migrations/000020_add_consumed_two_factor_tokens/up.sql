@@ -0,0 +1,10 @@
+-- 创建已使用的两步验证待完成令牌表，按jti记录，防止令牌在有效期内被重放
+CREATE TABLE consumed_two_factor_tokens (
+    jti VARCHAR(64) PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    -- 令牌过期后记录即可删除
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_consumed_two_factor_tokens_expires_at ON consumed_two_factor_tokens(expires_at);
@@ -0,0 +1,3 @@
+-- 恢复会话令牌长度限制（超长令牌对应的会话会被删除）
+DELETE FROM online_users WHERE LENGTH(session_token) > 255;
+ALTER TABLE online_users ALTER COLUMN session_token TYPE VARCHAR(255);
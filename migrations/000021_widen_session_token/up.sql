@@ -0,0 +1,2 @@
+-- 访问令牌使用EdDSA签名并带kid、jti时超过255个字符，会话令牌改为TEXT
+ALTER TABLE online_users ALTER COLUMN session_token TYPE TEXT;
@@ -0,0 +1,6 @@
+-- 删除错误次数字段，哈希后的验证码无法还原，未完成的请求全部作废
+ALTER TABLE email_change_requests DROP COLUMN IF EXISTS failed_attempts;
+UPDATE email_change_requests SET cancelled_at = CURRENT_TIMESTAMP
+WHERE confirmed_at IS NULL AND cancelled_at IS NULL;
+UPDATE email_change_requests SET code = '' WHERE LENGTH(code) > 6;
+ALTER TABLE email_change_requests ALTER COLUMN code TYPE VARCHAR(6);
@@ -0,0 +1,7 @@
+-- 邮箱变更验证码改为以HMAC-SHA256哈希存储，并记录错误次数
+ALTER TABLE email_change_requests ALTER COLUMN code TYPE VARCHAR(64);
+ALTER TABLE email_change_requests ADD COLUMN failed_attempts INTEGER NOT NULL DEFAULT 0;
+
+-- 作废所有未完成的明文验证码请求，用户需要重新申请
+UPDATE email_change_requests SET cancelled_at = CURRENT_TIMESTAMP
+WHERE confirmed_at IS NULL AND cancelled_at IS NULL;
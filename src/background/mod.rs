@@ -1,26 +1,42 @@
 use tokio::time::{interval, Duration};
 use crate::database::Pool;
-use crate::services::heartbeat::cleanup_inactive_users;
+use crate::services::heartbeat::{cleanup_inactive_users, flush_heartbeat_buffer};
 use log::info;
 
-// 后台清理任务
-pub async fn start_cleanup_task(pool: Pool, interval_minutes: u64) {
-    info!("Starting inactive user cleanup task, running every {} minutes", interval_minutes);
+// 后台清理任务：定期清理超过 上报间隔 × 倍数 未上报心跳的会话
+pub async fn start_cleanup_task(pool: Pool, cleanup_interval: Duration, timeout_multiplier: i32) {
+    info!("Starting inactive session cleanup task, running every {} seconds", cleanup_interval.as_secs());
     
-    let mut interval = interval(Duration::from_secs(interval_minutes * 60));
+    let mut interval = interval(cleanup_interval);
     
     loop {
         interval.tick().await;
         
-        info!("Running inactive user cleanup task");
+        info!("Running inactive session cleanup task");
         
-        match cleanup_inactive_users(&pool, interval_minutes as i64).await {
-            Ok(_) => {
-                info!("Inactive user cleanup task completed successfully");
+        match cleanup_inactive_users(&pool, timeout_multiplier).await {
+            Ok(expired) => {
+                info!("Inactive session cleanup task completed successfully, {} sessions timed out", expired);
             }
             Err(err) => {
-                log::error!("Failed to run inactive user cleanup task: {}", err);
+                log::error!("Failed to run inactive session cleanup task: {}", err);
             }
         }
     }
 }
+
+// 心跳写回任务：定期将缓冲中的心跳批量写回数据库
+pub async fn start_heartbeat_flush_task(pool: Pool, flush_interval: Duration) {
+    let flush_interval = flush_interval.max(Duration::from_secs(1));
+    info!("Starting heartbeat flush task, running every {} seconds", flush_interval.as_secs());
+    
+    let mut interval = interval(flush_interval);
+    
+    loop {
+        interval.tick().await;
+        
+        if let Err(err) = flush_heartbeat_buffer(&pool).await {
+            log::error!("Failed to flush buffered heartbeats: {}", err);
+        }
+    }
+}
@@ -1,6 +1,17 @@
 use dotenv::dotenv;
 use std::env;
+use std::sync::Arc;
 use std::time::Duration;
+use crate::utils::jwt_keys::JwtKeySet;
+
+/// 需要已验证邮箱的操作：登录
+pub const VERIFIED_EMAIL_ACTION_LOGIN: &str = "login";
+/// 需要已验证邮箱的操作：卡密充值
+pub const VERIFIED_EMAIL_ACTION_RECHARGE: &str = "recharge";
+/// 需要已验证邮箱的操作：软件访问（访问检查和许可证签发）
+pub const VERIFIED_EMAIL_ACTION_SOFTWARE_ACCESS: &str = "software_access";
+/// 需要已验证邮箱的操作：密码重置
+pub const VERIFIED_EMAIL_ACTION_PASSWORD_RESET: &str = "password_reset";
 
 /// 将字符串中的\n转换为实际换行符
 fn convert_newlines(input: String) -> String {
@@ -11,8 +22,17 @@ fn convert_newlines(input: String) -> String {
 pub struct Config {
     pub database_url: String,
     pub jwt_secret: String,
+    // JWT非对称签名配置
+    pub jwt_keys: Arc<JwtKeySet>,
+    pub jwt_accept_legacy_hs256: bool,
+    // 登录时下发给客户端的心跳上报间隔，会话超过 间隔 × 倍数 未上报心跳即视为超时
     pub heartbeat_interval: Duration,
+    pub heartbeat_timeout_multiplier: i32,
+    // 心跳先写入内存缓冲，按该间隔批量写回数据库
+    pub heartbeat_flush_interval: Duration,
     pub cleanup_interval: Duration,
+    // WebSocket推送连接的心跳（ping）间隔，超过3个间隔未收到客户端消息时断开
+    pub ws_ping_interval: Duration,
     pub server_port: u16,
     // HTTPS配置
     pub https_enabled: bool,
@@ -32,12 +52,54 @@ pub struct Config {
     pub email_verification_template: String,
     pub password_reset_subject: String,
     pub password_reset_template: String,
+    pub email_change_subject: String,
+    pub email_change_template: String,
+    pub email_change_notice_subject: String,
+    pub email_change_notice_template: String,
+    // 邮件发送限制配置
+    pub email_resend_cooldown: Duration,
+    pub email_daily_limit: i64,
+    pub email_code_max_attempts: i32,
+    // 对外访问地址，用于生成邮件中的链接
+    pub public_base_url: String,
+    // 密码重置页面地址，邮件中的重置链接会附加 token 参数
+    pub password_reset_link_url: String,
+    // 免密登录配置
+    pub magic_link_enabled: bool,
+    pub magic_link_ttl: Duration,
+    pub magic_link_url: String,
+    pub magic_link_subject: String,
+    pub magic_link_template: String,
     // 密码强度配置
     pub password_min_length: usize,
     pub password_require_uppercase: bool,
     pub password_require_lowercase: bool,
     pub password_require_digit: bool,
     pub password_require_special: bool,
+    pub password_min_score: u8,
+    pub password_breach_dir: String,
+    // 密码哈希配置（Argon2id）
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_parallelism: u32,
+    // 登录失败锁定配置
+    pub login_max_failures: i32,
+    pub login_failure_window: Duration,
+    pub login_lockout_duration: Duration,
+    pub login_lockout_max_duration: Duration,
+    // 硬件指纹配置：至少匹配该数量的组件时视为同一台设备
+    pub fingerprint_match_threshold: usize,
+    // 两步验证配置
+    pub totp_issuer: String,
+    // 离线许可证配置
+    pub license_offline_grace: Duration,
+    // 管理接口配置
+    pub admin_api_key: String,
+    // 客户端请求签名配置
+    pub request_signing_enabled: bool,
+    pub request_signing_max_skew: Duration,
+    // 需要已验证邮箱才能执行的操作
+    pub email_verification_required_for: Vec<String>,
 }
 
 impl Config {
@@ -47,9 +109,25 @@ impl Config {
         Self {
             database_url: env::var("DATABASE_URL").expect("DATABASE_URL must be set"),
             jwt_secret: env::var("JWT_SECRET").expect("JWT_SECRET must be set"),
+            // JWT非对称签名配置
+            jwt_keys: Arc::new(match env::var("JWT_KEYS_DIR") {
+                Ok(dir) if !dir.is_empty() => {
+                    let signing_kid = env::var("JWT_SIGNING_KEY_ID").ok().filter(|kid| !kid.is_empty());
+                    JwtKeySet::load(&dir, signing_kid.as_deref()).expect("Failed to load JWT keys")
+                },
+                _ => JwtKeySet::empty(),
+            }),
+            jwt_accept_legacy_hs256: env::var("JWT_ACCEPT_LEGACY_HS256").unwrap_or("true".to_string()).parse().unwrap_or(true),
             heartbeat_interval: Duration::from_secs(
                 env::var("HEARTBEAT_INTERVAL").unwrap_or("600".to_string()).parse().unwrap_or(600)
             ),
+            heartbeat_timeout_multiplier: env::var("HEARTBEAT_TIMEOUT_MULTIPLIER").unwrap_or("3".to_string()).parse::<i32>().unwrap_or(3).max(1),
+            heartbeat_flush_interval: Duration::from_secs(
+                env::var("HEARTBEAT_FLUSH_INTERVAL").unwrap_or("5".to_string()).parse().unwrap_or(5)
+            ),
+            ws_ping_interval: Duration::from_secs(
+                env::var("WS_PING_INTERVAL").unwrap_or("30".to_string()).parse().unwrap_or(30)
+            ),
             cleanup_interval: Duration::from_secs(
                 env::var("CLEANUP_INTERVAL").unwrap_or("300".to_string()).parse().unwrap_or(300)
             ),
@@ -79,7 +157,39 @@ impl Config {
             password_reset_subject: env::var("PASSWORD_RESET_SUBJECT").unwrap_or("Password Reset Request".to_string()),
             password_reset_template: convert_newlines(
                 env::var("PASSWORD_RESET_TEMPLATE").unwrap_or(
-                    "Hello {username},\n\nYou requested a password reset for your account. Your verification code is: {code}\n\nThis code will expire in {expiry}.\n\nPlease use this code with your username and email to reset your password.\n\nIf you didn't request this, please ignore this email.\n\nBest regards,\nRLServer Team".to_string()
+                    "Hello {username},\n\nYou requested a password reset for your account. Your verification code is: {code}\n\nThis code will expire in {expiry}.\n\nPlease use this code with your username and email to reset your password, or open the following link:\n{reset_link}\n\nIf you didn't request this, please ignore this email.\n\nBest regards,\nRLServer Team".to_string()
+                )
+            ),
+            email_change_subject: env::var("EMAIL_CHANGE_SUBJECT").unwrap_or("Confirm Your New Email Address".to_string()),
+            email_change_template: convert_newlines(
+                env::var("EMAIL_CHANGE_TEMPLATE").unwrap_or(
+                    "Hello {username},\n\nYou requested to change your account email to this address. Your verification code is: {code}\n\nThis code will expire in {expiry}.\n\nIf you didn't request this, please ignore this email.\n\nBest regards,\nRLServer Team".to_string()
+                )
+            ),
+            email_change_notice_subject: env::var("EMAIL_CHANGE_NOTICE_SUBJECT").unwrap_or("Email Change Requested".to_string()),
+            email_change_notice_template: convert_newlines(
+                env::var("EMAIL_CHANGE_NOTICE_TEMPLATE").unwrap_or(
+                    "Hello {username},\n\nA request was made to change your account email to {new_email}. The change will take effect once the new address is confirmed.\n\nIf you didn't request this, cancel it within {expiry} using the link below and change your password:\n{cancel_url}\n\nBest regards,\nRLServer Team".to_string()
+                )
+            ),
+            // 邮件发送限制配置
+            email_resend_cooldown: Duration::from_secs(
+                env::var("EMAIL_RESEND_COOLDOWN").unwrap_or("60".to_string()).parse().unwrap_or(60)
+            ),
+            email_daily_limit: env::var("EMAIL_DAILY_LIMIT").unwrap_or("10".to_string()).parse().unwrap_or(10),
+            email_code_max_attempts: env::var("EMAIL_CODE_MAX_ATTEMPTS").unwrap_or("5".to_string()).parse().unwrap_or(5),
+            public_base_url: env::var("PUBLIC_BASE_URL").unwrap_or("http://localhost:28001".to_string()),
+            password_reset_link_url: env::var("PASSWORD_RESET_LINK_URL").unwrap_or("http://localhost:28001/reset-password".to_string()),
+            // 免密登录配置
+            magic_link_enabled: env::var("MAGIC_LINK_ENABLED").unwrap_or("false".to_string()).parse().unwrap_or(false),
+            magic_link_ttl: Duration::from_secs(
+                env::var("MAGIC_LINK_TTL").unwrap_or("600".to_string()).parse().unwrap_or(600)
+            ),
+            magic_link_url: env::var("MAGIC_LINK_URL").unwrap_or("rlserver://magic-login".to_string()),
+            magic_link_subject: env::var("MAGIC_LINK_SUBJECT").unwrap_or("Your Login Link".to_string()),
+            magic_link_template: convert_newlines(
+                env::var("MAGIC_LINK_TEMPLATE").unwrap_or(
+                    "Hello {username},\n\nUse the following link to sign in on the device that requested it:\n{login_link}\n\nThe link can only be used once and will expire in {expiry}.\n\nIf you didn't request this, please ignore this email.\n\nBest regards,\nRLServer Team".to_string()
                 )
             ),
             // 密码强度配置
@@ -88,6 +198,49 @@ impl Config {
             password_require_lowercase: env::var("PASSWORD_REQUIRE_LOWERCASE").unwrap_or("true".to_string()).parse().unwrap_or(true),
             password_require_digit: env::var("PASSWORD_REQUIRE_DIGIT").unwrap_or("true".to_string()).parse().unwrap_or(true),
             password_require_special: env::var("PASSWORD_REQUIRE_SPECIAL").unwrap_or("true".to_string()).parse().unwrap_or(true),
+            password_min_score: env::var("PASSWORD_MIN_SCORE").unwrap_or("3".to_string()).parse().unwrap_or(3),
+            password_breach_dir: env::var("PASSWORD_BREACH_DIR").unwrap_or("".to_string()),
+            // 密码哈希配置（Argon2id）
+            argon2_memory_kib: env::var("ARGON2_MEMORY_KIB").unwrap_or("19456".to_string()).parse().unwrap_or(19456),
+            argon2_iterations: env::var("ARGON2_ITERATIONS").unwrap_or("2".to_string()).parse().unwrap_or(2),
+            argon2_parallelism: env::var("ARGON2_PARALLELISM").unwrap_or("1".to_string()).parse().unwrap_or(1),
+            // 登录失败锁定配置
+            login_max_failures: env::var("LOGIN_MAX_FAILURES").unwrap_or("5".to_string()).parse().unwrap_or(5),
+            login_failure_window: Duration::from_secs(
+                env::var("LOGIN_FAILURE_WINDOW").unwrap_or("900".to_string()).parse().unwrap_or(900)
+            ),
+            login_lockout_duration: Duration::from_secs(
+                env::var("LOGIN_LOCKOUT_DURATION").unwrap_or("300".to_string()).parse().unwrap_or(300)
+            ),
+            login_lockout_max_duration: Duration::from_secs(
+                env::var("LOGIN_LOCKOUT_MAX_DURATION").unwrap_or("86400".to_string()).parse().unwrap_or(86400)
+            ),
+            // 硬件指纹配置
+            fingerprint_match_threshold: env::var("FINGERPRINT_MATCH_THRESHOLD").unwrap_or("3".to_string()).parse().unwrap_or(3),
+            // 两步验证配置
+            totp_issuer: env::var("TOTP_ISSUER").unwrap_or("RLServer".to_string()),
+            // 离线许可证配置
+            license_offline_grace: Duration::from_secs(
+                env::var("LICENSE_OFFLINE_GRACE").unwrap_or("259200".to_string()).parse().unwrap_or(259200)
+            ),
+            // 管理接口配置
+            admin_api_key: env::var("ADMIN_API_KEY").unwrap_or_default(),
+            // 客户端请求签名配置
+            request_signing_enabled: env::var("REQUEST_SIGNING_ENABLED").unwrap_or("false".to_string()).parse().unwrap_or(false),
+            request_signing_max_skew: Duration::from_secs(
+                env::var("REQUEST_SIGNING_MAX_SKEW").unwrap_or("300".to_string()).parse().unwrap_or(300)
+            ),
+            // 需要已验证邮箱才能执行的操作，逗号分隔
+            email_verification_required_for: env::var("EMAIL_VERIFICATION_REQUIRED_FOR").unwrap_or_default()
+                .split(',')
+                .map(|action| action.trim().to_lowercase())
+                .filter(|action| !action.is_empty())
+                .collect(),
         }
     }
+    
+    /// 指定操作是否要求邮箱已验证
+    pub fn requires_verified_email(&self, action: &str) -> bool {
+        self.email_verification_required_for.iter().any(|required| required == action)
+    }
 }
\ No newline at end of file
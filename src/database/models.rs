@@ -24,6 +24,16 @@ pub struct User {
     pub updated_at: DateTime<Utc>,
 }
 
+impl User {
+    /// 当前生效的VIP等级，VIP过期后视为0级
+    pub fn current_vip_level(&self) -> i32 {
+        match self.vip_expires_at {
+            Some(expires_at) if expires_at > Utc::now() => self.vip_level,
+            _ => 0,
+        }
+    }
+}
+
 // 软件表
 #[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Insertable, AsChangeset)]
 #[diesel(table_name = crate::schema::software)]
@@ -78,17 +88,34 @@ pub struct RechargeLog {
 #[diesel(treat_none_as_null = true)]
 pub struct LoginLog {
     pub id: i32,
-    pub user_id: i32,
+    pub user_id: Option<i32>,
     pub login_time: DateTime<Utc>,
     pub hardware_code: String,
     pub software_version: String,
     pub ip_address: String,
     pub status: String,
     pub created_at: DateTime<Utc>,
+    pub username: String,
+    pub failure_reason: Option<String>,
 }
 
-// 在线用户表
+// 登录锁定表
 #[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::login_lockouts)]
+#[diesel(treat_none_as_null = true)]
+pub struct LoginLockout {
+    pub id: i32,
+    pub scope: String,
+    pub scope_key: String,
+    pub failure_count: i32,
+    pub window_started_at: DateTime<Utc>,
+    pub lockout_count: i32,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// 在线用户表
+#[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, Insertable, AsChangeset)]
 #[diesel(table_name = crate::schema::online_users)]
 #[diesel(treat_none_as_null = true)]
 pub struct OnlineUser {
@@ -104,6 +131,14 @@ pub struct OnlineUser {
     pub created_at: DateTime<Utc>,
 }
 
+impl OnlineUser {
+    /// 会话是否已超时：超过 上报间隔（秒）× 倍数 未上报心跳
+    pub fn is_expired(&self, timeout_multiplier: i32) -> bool {
+        let timeout = chrono::Duration::seconds(self.status_interval as i64 * timeout_multiplier as i64);
+        self.last_activity_at + timeout < Utc::now()
+    }
+}
+
 // 注册请求DTO
 #[derive(Debug, Deserialize, Validate)]
 pub struct RegisterRequest {
@@ -137,6 +172,48 @@ pub struct LoginRequest {
     
     #[validate(length(min = 1, max = 50, message = "Software version must be between 1 and 50 characters"))]
     pub software_version: String,
+    
+    #[validate]
+    pub fingerprint: Option<HardwareFingerprint>,
+}
+
+// 客户端上报的结构化硬件指纹，各组件均可选，服务器按匹配的组件数识别同一台设备
+#[derive(Debug, Clone, Deserialize, Validate)]
+pub struct HardwareFingerprint {
+    #[validate(length(min = 1, max = 255, message = "CPU identifier must be between 1 and 255 characters"))]
+    pub cpu: Option<String>,
+    
+    #[validate(length(min = 1, max = 255, message = "Board identifier must be between 1 and 255 characters"))]
+    pub board: Option<String>,
+    
+    #[validate(length(min = 1, max = 255, message = "Disk identifier must be between 1 and 255 characters"))]
+    pub disk: Option<String>,
+    
+    #[validate(length(min = 1, max = 255, message = "MAC address must be between 1 and 255 characters"))]
+    pub mac: Option<String>,
+    
+    #[validate(length(min = 1, max = 255, message = "BIOS identifier must be between 1 and 255 characters"))]
+    pub bios: Option<String>,
+    
+    #[validate(length(min = 1, max = 255, message = "GPU identifier must be between 1 and 255 characters"))]
+    pub gpu: Option<String>,
+}
+
+impl HardwareFingerprint {
+    /// 已上报的组件列表（组件名, 值）
+    pub fn components(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("cpu", &self.cpu),
+            ("board", &self.board),
+            ("disk", &self.disk),
+            ("mac", &self.mac),
+            ("bios", &self.bios),
+            ("gpu", &self.gpu),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.as_deref().map(|value| (name, value)))
+        .collect()
+    }
 }
 
 // 密码重置请求DTO
@@ -162,6 +239,86 @@ pub struct VerifyResetPasswordRequest {
     pub new_password: String,
 }
 
+// 申请免密登录链接DTO
+#[derive(Debug, Deserialize, Validate)]
+pub struct MagicLinkRequest {
+    #[validate(email(message = "Invalid email format"))]
+    pub email: String,
+    
+    #[validate(length(min = 1, max = 100, message = "Hardware code must be between 1 and 100 characters"))]
+    pub hardware_code: String,
+}
+
+// 使用免密登录链接登录DTO
+#[derive(Debug, Deserialize, Validate)]
+pub struct RedeemMagicLinkRequest {
+    #[validate(length(min = 1, message = "Token must not be empty"))]
+    pub token: String,
+    
+    #[validate(length(min = 1, max = 100, message = "Hardware code must be between 1 and 100 characters"))]
+    pub hardware_code: String,
+    
+    #[validate(length(min = 1, max = 50, message = "Software version must be between 1 and 50 characters"))]
+    pub software_version: String,
+    
+    #[validate]
+    pub fingerprint: Option<HardwareFingerprint>,
+}
+
+// 通过重置链接设置新密码DTO
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResetPasswordWithTokenRequest {
+    #[validate(length(min = 1, message = "Token must not be empty"))]
+    pub token: String,
+    
+    #[validate(length(min = 8, message = "Password must be at least 8 characters"))]
+    pub new_password: String,
+}
+
+// 修改密码DTO
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangePasswordRequest {
+    #[validate(length(min = 1, message = "Current password must not be empty"))]
+    pub current_password: String,
+    
+    #[validate(length(min = 8, message = "Password must be at least 8 characters"))]
+    pub new_password: String,
+}
+
+// 申请修改邮箱DTO
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangeEmailRequest {
+    #[validate(email(message = "Invalid email format"))]
+    pub new_email: String,
+    
+    #[validate(length(min = 1, message = "Password must not be empty"))]
+    pub password: String,
+}
+
+// 确认修改邮箱DTO
+#[derive(Debug, Deserialize, Validate)]
+pub struct ConfirmEmailChangeRequest {
+    #[validate(length(min = 1, message = "Verification code must not be empty"))]
+    pub code: String,
+}
+
+// 取消修改邮箱DTO（旧邮箱中的取消链接）
+#[derive(Debug, Deserialize, Validate)]
+pub struct CancelEmailChangeQuery {
+    #[validate(length(min = 1, message = "Token must not be empty"))]
+    pub token: String,
+}
+
+// 登录历史分页查询DTO
+#[derive(Debug, Deserialize, Validate)]
+pub struct LoginHistoryQuery {
+    #[validate(range(min = 1, max = 10000, message = "Page must be between 1 and 10000"))]
+    pub page: Option<i64>,
+    
+    #[validate(range(min = 1, max = 100, message = "Page size must be between 1 and 100"))]
+    pub page_size: Option<i64>,
+}
+
 // 充值请求DTO
 #[derive(Debug, Deserialize)]
 pub struct RechargeRequest {
@@ -169,11 +326,18 @@ pub struct RechargeRequest {
 }
 
 // 心跳请求DTO
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate)]
 pub struct HeartbeatRequest {
     pub session_token: String,
     pub hardware_code: String,
     pub software_version: String,
+    
+    #[validate]
+    pub fingerprint: Option<HardwareFingerprint>,
+    
+    // 客户端已处理的会话指令ID
+    #[serde(default)]
+    pub acknowledged_command_ids: Vec<i32>,
 }
 
 // 退出登录请求DTO
@@ -195,11 +359,368 @@ pub struct Blacklist {
     pub created_at: DateTime<Utc>,
 }
 
+// 刷新令牌表
+#[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::refresh_tokens)]
+#[diesel(treat_none_as_null = true)]
+pub struct RefreshToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub session_id: i32,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+// VIP等级策略表
+#[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::vip_level_policies)]
+#[diesel(primary_key(vip_level))]
+#[diesel(treat_none_as_null = true)]
+pub struct VipLevelPolicy {
+    pub vip_level: i32,
+    pub max_sessions: i32,
+    pub session_limit_action: String,
+    pub allow_shared_hardware: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub max_devices: i32,
+    pub unbind_cooldown_hours: i32,
+    pub unbind_penalty_hours: i32,
+}
+
+impl VipLevelPolicy {
+    /// 未配置任何策略时使用的默认策略：单设备登录，踢掉旧会话，不限制绑定设备数
+    pub fn default_for(vip_level: i32) -> Self {
+        Self {
+            vip_level,
+            max_sessions: 1,
+            session_limit_action: "kick_oldest".to_string(),
+            allow_shared_hardware: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            max_devices: 0,
+            unbind_cooldown_hours: 24,
+            unbind_penalty_hours: 0,
+        }
+    }
+}
+
+// TOTP两步验证表
+#[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::user_totp)]
+#[diesel(treat_none_as_null = true)]
+pub struct UserTotp {
+    pub id: i32,
+    pub user_id: i32,
+    pub secret: String,
+    pub enabled: bool,
+    pub last_used_step: Option<i64>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// 两步验证备用码表
+#[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::totp_backup_codes)]
+#[diesel(treat_none_as_null = true)]
+pub struct TotpBackupCode {
+    pub id: i32,
+    pub user_id: i32,
+    pub code_hash: String,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+// 两步验证码请求DTO
+#[derive(Debug, Deserialize, Validate)]
+pub struct TwoFactorCodeRequest {
+    #[validate(length(min = 1, max = 20, message = "Code must be between 1 and 20 characters"))]
+    pub code: String,
+}
+
+// 关闭两步验证请求DTO
+#[derive(Debug, Deserialize, Validate)]
+pub struct DisableTwoFactorRequest {
+    #[validate(length(min = 1, message = "Password must not be empty"))]
+    pub password: String,
+    
+    #[validate(length(min = 1, max = 20, message = "Code must be between 1 and 20 characters"))]
+    pub code: String,
+}
+
+// 两步验证登录请求DTO
+#[derive(Debug, Deserialize, Validate)]
+pub struct VerifyTwoFactorLoginRequest {
+    #[validate(length(min = 1, message = "Pending token must not be empty"))]
+    pub pending_token: String,
+    
+    #[validate(length(min = 1, max = 20, message = "Code must be between 1 and 20 characters"))]
+    pub code: String,
+    
+    #[validate(length(min = 1, max = 100, message = "Hardware code must be between 1 and 100 characters"))]
+    pub hardware_code: String,
+    
+    #[validate(length(min = 1, max = 50, message = "Software version must be between 1 and 50 characters"))]
+    pub software_version: String,
+    
+    #[validate]
+    pub fingerprint: Option<HardwareFingerprint>,
+}
+
+// 离线激活记录表
+#[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::offline_activations)]
+#[diesel(treat_none_as_null = true)]
+pub struct OfflineActivation {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    pub software_id: i32,
+    pub hardware_code: String,
+    pub request_nonce: String,
+    pub source: String,
+    pub card_code: Option<String>,
+    pub vip_level: i32,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+// 邮箱变更请求表
+#[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::email_change_requests)]
+#[diesel(treat_none_as_null = true)]
+pub struct EmailChangeRequest {
+    pub id: i32,
+    pub user_id: i32,
+    pub old_email: String,
+    pub new_email: String,
+    pub code: String,
+    pub cancel_token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub failed_attempts: i32,
+}
+
+// 邮件发送记录表
+#[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::email_dispatch_logs)]
+#[diesel(treat_none_as_null = true)]
+pub struct EmailDispatchLog {
+    pub id: i32,
+    pub user_id: i32,
+    pub purpose: String,
+    pub email: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// 免密登录链接表
+#[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::magic_link_tokens)]
+#[diesel(treat_none_as_null = true)]
+pub struct MagicLinkToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub token_hash: String,
+    pub hardware_code: String,
+    pub ip_address: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+// 用户绑定设备表
+#[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::user_devices)]
+#[diesel(treat_none_as_null = true)]
+pub struct UserDevice {
+    pub id: i32,
+    pub user_id: i32,
+    pub hardware_code: String,
+    pub bound_at: DateTime<Utc>,
+    pub last_login_at: DateTime<Utc>,
+    pub unbound_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+// 设备硬件指纹组件表
+#[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::device_fingerprint_components)]
+#[diesel(treat_none_as_null = true)]
+pub struct DeviceFingerprintComponent {
+    pub id: i32,
+    pub device_id: i32,
+    pub component: String,
+    pub value: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// 硬件指纹变更审计表
+#[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::device_fingerprint_changes)]
+#[diesel(treat_none_as_null = true)]
+pub struct DeviceFingerprintChange {
+    pub id: i32,
+    pub device_id: i32,
+    pub component: String,
+    pub old_value: Option<String>,
+    pub new_value: String,
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// 会话指令队列表
+#[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize, Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::session_commands)]
+#[diesel(treat_none_as_null = true)]
+pub struct SessionCommand {
+    pub id: i32,
+    pub session_id: i32,
+    pub user_id: i32,
+    pub command_type: String,
+    pub message: Option<String>,
+    pub payload: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+// 会话历史表
+#[derive(Queryable, Identifiable, Debug, Serialize, Deserialize, Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::session_history)]
+#[diesel(treat_none_as_null = true)]
+pub struct SessionHistory {
+    pub id: i32,
+    pub session_id: i32,
+    pub user_id: i32,
+    pub hardware_code: String,
+    pub software_version: String,
+    pub ip_address: String,
+    pub started_at: DateTime<Utc>,
+    pub last_activity_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub end_reason: String,
+    pub duration_seconds: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+// 管理员下发会话指令DTO
+#[derive(Debug, Deserialize, Validate)]
+pub struct EnqueueSessionCommandRequest {
+    pub user_id: i32,
+    
+    // 指定会话ID时只发给该会话，否则发给该用户当前所有在线会话
+    pub session_id: Option<i32>,
+    
+    #[validate(length(min = 1, max = 30, message = "Command type must be between 1 and 30 characters"))]
+    pub command_type: String,
+    
+    #[validate(length(max = 2000, message = "Message must be at most 2000 characters"))]
+    pub message: Option<String>,
+    
+    pub payload: Option<serde_json::Value>,
+    
+    // 指令有效期（秒），过期后不再下发
+    #[validate(range(min = 1, max = 2592000, message = "TTL must be between 1 and 2592000 seconds"))]
+    pub ttl_seconds: Option<i64>,
+}
+
+// 管理员向所有在线会话广播指令DTO（公告、更新提醒）
+#[derive(Debug, Deserialize, Validate)]
+pub struct BroadcastSessionCommandRequest {
+    #[validate(length(min = 1, max = 30, message = "Command type must be between 1 and 30 characters"))]
+    pub command_type: String,
+    
+    #[validate(length(max = 2000, message = "Message must be at most 2000 characters"))]
+    pub message: Option<String>,
+    
+    pub payload: Option<serde_json::Value>,
+    
+    // 指令有效期（秒），过期后不再下发
+    #[validate(range(min = 1, max = 2592000, message = "TTL must be between 1 and 2592000 seconds"))]
+    pub ttl_seconds: Option<i64>,
+}
+
+// 离线激活请求码内容（客户端生成，Base64URL编码的JSON）
+#[derive(Debug, Deserialize, Validate)]
+pub struct ActivationRequestCode {
+    #[validate(length(min = 1, max = 100, message = "Hardware code must be between 1 and 100 characters"))]
+    pub hardware_code: String,
+    
+    pub software_id: i32,
+    
+    #[validate(length(min = 8, max = 64, message = "Nonce must be between 8 and 64 characters"))]
+    pub nonce: String,
+}
+
+// 用户提交离线激活请求DTO
+#[derive(Debug, Deserialize, Validate)]
+pub struct OfflineActivationRequest {
+    #[validate(length(min = 1, max = 1024, message = "Request code must be between 1 and 1024 characters"))]
+    pub request_code: String,
+    
+    // 消耗的充值卡密，不填时消耗账号VIP时长
+    pub card_code: Option<String>,
+    
+    // 消耗的VIP天数，不使用卡密时必填
+    #[validate(range(min = 1, max = 3650, message = "Days must be between 1 and 3650"))]
+    pub days: Option<i32>,
+}
+
+// 管理员提交离线激活请求DTO
+#[derive(Debug, Deserialize, Validate)]
+pub struct AdminOfflineActivationRequest {
+    #[validate(length(min = 1, max = 1024, message = "Request code must be between 1 and 1024 characters"))]
+    pub request_code: String,
+    
+    pub card_code: Option<String>,
+    
+    // 不使用卡密时，消耗该用户的VIP时长
+    pub user_id: Option<i32>,
+    
+    #[validate(range(min = 1, max = 3650, message = "Days must be between 1 and 3650"))]
+    pub days: Option<i32>,
+}
+
 // JWT Claims
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String, // 用户ID
     pub username: String,
     pub exp: i64, // 过期时间
-    pub token_type: String, // 令牌类型：access或refresh
+    pub token_type: String, // 令牌类型：access、refresh、reset、activation或2fa_pending
+    #[serde(default)]
+    pub jti: String, // 令牌唯一标识，保证同一秒内签发的令牌也互不相同
+}
+
+// 离线许可证载荷，客户端可用JWKS公钥在本地验证
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LicenseClaims {
+    pub sub: String, // 用户ID
+    pub token_type: String, // 固定为license
+    pub hardware_code: String, // 绑定的硬件码
+    pub software_id: i32,
+    pub vip_level: i32,
+    pub vip_expires_at: Option<i64>, // VIP到期时间
+    pub iat: i64, // 签发时间
+    pub exp: i64, // 离线可用截止时间，不晚于VIP到期时间
+    pub jti: String,
+}
+
+// 离线激活码载荷，客户端验证后可转换为本地许可证文件
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OfflineActivationClaims {
+    pub sub: String, // 用户ID，管理员使用卡密激活时为空
+    pub token_type: String, // 固定为offline_activation
+    pub hardware_code: String,
+    pub software_id: i32,
+    pub vip_level: i32,
+    pub nonce: String, // 请求码中的随机数，客户端据此确认激活码对应本机发起的请求
+    pub iat: i64,
+    pub exp: i64, // 激活到期时间
 }
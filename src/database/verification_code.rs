@@ -15,4 +15,6 @@ pub struct VerificationCode {
     pub expires_at: DateTime<Utc>,
     pub used: bool,
     pub created_at: DateTime<Utc>,
+    pub failed_attempts: i32,
+    pub purpose: String,
 }
\ No newline at end of file
@@ -13,6 +13,14 @@ pub struct ErrorResponse {
     pub details: Option<String>,
 }
 
+/// 邮箱未验证时响应中的错误代码
+pub const EMAIL_NOT_VERIFIED_CODE: &str = "email_not_verified";
+
+/// 邮箱未验证的响应，附带错误代码以便客户端跳转到邮箱验证流程
+pub fn email_not_verified_response(msg: &str) -> HttpResponse {
+    HttpResponse::Forbidden().json(serde_json::json!({ "error": msg, "code": EMAIL_NOT_VERIFIED_CODE }))
+}
+
 /// 自定义错误类型
 #[derive(Debug)]
 pub enum AppError {
@@ -24,6 +32,10 @@ pub enum AppError {
     Forbidden(String),
     /// 资源未找到
     NotFound(String),
+    /// 请求过于频繁或已被临时锁定
+    TooManyRequests(String),
+    /// 邮箱未验证，客户端应引导用户完成邮箱验证
+    EmailNotVerified(String),
     /// 内部服务器错误
     InternalServerError(String),
     /// 数据库错误
@@ -41,6 +53,8 @@ impl fmt::Display for AppError {
             AppError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
             AppError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
             AppError::NotFound(msg) => write!(f, "Not Found: {}", msg),
+            AppError::TooManyRequests(msg) => write!(f, "Too Many Requests: {}", msg),
+            AppError::EmailNotVerified(msg) => write!(f, "Email Not Verified: {}", msg),
             AppError::InternalServerError(msg) => write!(f, "Internal Server Error: {}", msg),
             AppError::DatabaseError(msg) => write!(f, "Database Error: {}", msg),
             AppError::JwtError(msg) => write!(f, "JWT Error: {}", msg),
@@ -56,6 +70,8 @@ impl ResponseError for AppError {
             AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
             AppError::Forbidden(_) => StatusCode::FORBIDDEN,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::EmailNotVerified(_) => StatusCode::FORBIDDEN,
             AppError::InternalServerError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::JwtError(_) => StatusCode::UNAUTHORIZED,
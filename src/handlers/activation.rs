@@ -0,0 +1,82 @@
+use actix_web::{web, Responder, HttpResponse, HttpMessage};
+use serde::Serialize;
+use validator::Validate;
+use crate::database::models::*;
+use crate::services::activation::activate_offline;
+use crate::services::auth::check_email_verified;
+use crate::database::Pool;
+use crate::config::{Config, VERIFIED_EMAIL_ACTION_SOFTWARE_ACCESS};
+use crate::errors::{AppError, email_not_verified_response};
+
+#[derive(Debug, Serialize)]
+struct OfflineActivationResponse {
+    activation_code: String,
+    software_id: i32,
+    hardware_code: String,
+    vip_level: i32,
+    source: String,
+    expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+// 用户提交离线激活请求码
+pub async fn offline_activation_handler(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    req: web::Json<OfflineActivationRequest>,
+    req_ext: actix_web::HttpRequest,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = req.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+
+    // 从请求扩展中获取用户ID
+    let user_id = if let Some(user_id) = req_ext.extensions().get::<i32>() {
+        *user_id
+    } else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" }));
+    };
+
+    // 按配置检查邮箱是否已验证
+    match check_email_verified(&pool, user_id, VERIFIED_EMAIL_ACTION_SOFTWARE_ACCESS, &config).await {
+        Ok(_) => {}
+        Err(AppError::EmailNotVerified(msg)) => return email_not_verified_response(&msg),
+        Err(err) => return HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() })),
+    }
+
+    let result = activate_offline(&pool, &req.request_code, Some(user_id), req.card_code.as_deref(), req.days, &config).await;
+    activation_response(result)
+}
+
+// 管理员代为处理离线激活请求码
+pub async fn admin_offline_activation_handler(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    req: web::Json<AdminOfflineActivationRequest>,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = req.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+
+    let result = activate_offline(&pool, &req.request_code, req.user_id, req.card_code.as_deref(), req.days, &config).await;
+    activation_response(result)
+}
+
+// 将激活结果转换为HTTP响应
+fn activation_response(result: Result<(String, OfflineActivation), AppError>) -> HttpResponse {
+    match result {
+        Ok((activation_code, activation)) => HttpResponse::Ok().json(OfflineActivationResponse {
+            activation_code,
+            software_id: activation.software_id,
+            hardware_code: activation.hardware_code,
+            vip_level: activation.vip_level,
+            source: activation.source,
+            expires_at: activation.expires_at,
+        }),
+        Err(AppError::BadRequest(msg)) => HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })),
+        Err(AppError::Forbidden(msg)) => HttpResponse::Forbidden().json(serde_json::json!({ "error": msg })),
+        Err(AppError::NotFound(msg)) => HttpResponse::NotFound().json(serde_json::json!({ "error": msg })),
+        Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
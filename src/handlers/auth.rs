@@ -5,7 +5,7 @@ use crate::database::models::*;
 use crate::services::auth::*;
 use crate::database::Pool;
 use crate::config::Config;
-use crate::errors::AppError;
+use crate::errors::{AppError, email_not_verified_response};
 
 #[derive(Debug, Serialize)]
 struct RegisterResponse {
@@ -17,8 +17,73 @@ struct RegisterResponse {
 struct LoginResponse {
     message: String,
     token: String,
+    refresh_token: String,
     vip_level: i32,
     vip_expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    status_interval: i32,
+}
+
+#[derive(Debug, Serialize)]
+struct EmailVerificationRequiredResponse {
+    error: String,
+    code: String,
+    activation_token: String,
+}
+
+#[derive(Debug, Serialize)]
+struct TwoFactorRequiredResponse {
+    message: String,
+    two_factor_required: bool,
+    pending_token: String,
+}
+
+// 登录成功响应
+fn login_success_response(user: User, tokens: SessionTokens) -> HttpResponse {
+    HttpResponse::Ok().json(LoginResponse {
+        message: "Login successful".to_string(),
+        token: tokens.access_token,
+        refresh_token: tokens.refresh_token,
+        vip_level: user.vip_level,
+        vip_expires_at: user.vip_expires_at,
+        status_interval: tokens.status_interval,
+    })
+}
+
+// 根据登录结果生成响应
+fn login_outcome_response(result: Result<LoginOutcome, AppError>) -> HttpResponse {
+    match result {
+        Ok(LoginOutcome::Authenticated(user, tokens)) => login_success_response(*user, tokens),
+        Ok(LoginOutcome::TwoFactorRequired(pending_token)) => {
+            HttpResponse::Ok().json(TwoFactorRequiredResponse {
+                message: "Two-factor authentication required".to_string(),
+                two_factor_required: true,
+                pending_token,
+            })
+        }
+        Ok(LoginOutcome::EmailVerificationRequired(activation_token)) => {
+            HttpResponse::Forbidden().json(EmailVerificationRequiredResponse {
+                error: "Email address has not been verified".to_string(),
+                code: crate::errors::EMAIL_NOT_VERIFIED_CODE.to_string(),
+                activation_token,
+            })
+        }
+        Err(err) => login_error_response(err),
+    }
+}
+
+// 登录失败响应
+fn login_error_response(err: AppError) -> HttpResponse {
+    match err {
+        AppError::Forbidden(msg) => {
+            HttpResponse::Forbidden().json(serde_json::json!({ "error": msg }))
+        }
+        AppError::TooManyRequests(msg) => {
+            HttpResponse::TooManyRequests().json(serde_json::json!({ "error": msg }))
+        }
+        err => {
+            HttpResponse::Unauthorized().json(serde_json::json!({ "error": err.to_string() }))
+        }
+    }
 }
 
 // 用户注册
@@ -62,21 +127,76 @@ pub async fn login_handler(
     let conn_info = req_addr.connection_info();
     let ip = conn_info.realip_remote_addr().unwrap_or("0.0.0.0");
     
-    match login_user(&pool, req.into_inner(), ip, &config).await {
-        Ok((user, token)) => {
-            HttpResponse::Ok().json(LoginResponse {
-                message: "Login successful".to_string(),
-                token,
-                vip_level: user.vip_level,
-                vip_expires_at: user.vip_expires_at,
-            })
+    login_outcome_response(login_user(&pool, req.into_inner(), ip, &config).await)
+}
+
+// 申请免密登录链接
+pub async fn request_magic_link_handler(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    req: web::Json<MagicLinkRequest>,
+    req_addr: actix_web::HttpRequest,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = req.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+    
+    // 获取客户端IP
+    let ip = req_addr.connection_info().realip_remote_addr().unwrap_or("0.0.0.0").to_string();
+    
+    match request_magic_link(&pool, req.into_inner(), &ip, &config).await {
+        Ok(_) => {
+            HttpResponse::Ok().json(serde_json::json!({ "message": "If the email is registered, a login link has been sent" }))
+        }
+        Err(AppError::Forbidden(msg)) => {
+            HttpResponse::Forbidden().json(serde_json::json!({ "error": msg }))
         }
         Err(err) => {
-            HttpResponse::Unauthorized().json(serde_json::json!({ "error": err.to_string() }))
+            HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }))
         }
     }
 }
 
+// 使用免密登录链接登录
+pub async fn redeem_magic_link_handler(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    req: web::Json<RedeemMagicLinkRequest>,
+    req_addr: actix_web::HttpRequest,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = req.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+    
+    // 获取客户端IP
+    let ip = req_addr.connection_info().realip_remote_addr().unwrap_or("0.0.0.0").to_string();
+    
+    login_outcome_response(redeem_magic_link(&pool, req.into_inner(), &ip, &config).await)
+}
+
+// 两步验证登录
+pub async fn verify_two_factor_login_handler(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    req: web::Json<VerifyTwoFactorLoginRequest>,
+    req_addr: actix_web::HttpRequest,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = req.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+    
+    // 获取客户端IP
+    let ip = req_addr.connection_info().realip_remote_addr().unwrap_or("0.0.0.0").to_string();
+    
+    match complete_two_factor_login(&pool, req.into_inner(), &ip, &config).await {
+        Ok((user, tokens)) => login_success_response(user, tokens),
+        Err(err) => login_error_response(err),
+    }
+}
+
 // 用户登出
 pub async fn logout_handler(
     pool: web::Data<Pool>,
@@ -116,12 +236,14 @@ pub async fn refresh_token_handler(
     req: web::Json<RefreshTokenRequest>,
 ) -> impl Responder {
     match refresh_access_token(&pool, &req.refresh_token, &config).await {
-        Ok((user, token)) => {
+        Ok((user, tokens)) => {
             HttpResponse::Ok().json(LoginResponse {
                 message: "Token refreshed successfully".to_string(),
-                token,
+                token: tokens.access_token,
+                refresh_token: tokens.refresh_token,
                 vip_level: user.vip_level,
                 vip_expires_at: user.vip_expires_at,
+                status_interval: tokens.status_interval,
             })
         }
         Err(err) => {
@@ -145,6 +267,10 @@ pub async fn reset_password_handler(
         Ok(_) => {
             HttpResponse::Ok().json(serde_json::json!({ "message": "Password reset email sent successfully" }))
         }
+        Err(AppError::EmailNotVerified(msg)) => email_not_verified_response(&msg),
+        Err(AppError::TooManyRequests(msg)) => {
+            HttpResponse::TooManyRequests().json(serde_json::json!({ "error": msg }))
+        }
         Err(err) => {
             HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }))
         }
@@ -163,6 +289,30 @@ pub async fn verify_reset_password_handler(
     }
     
     match verify_reset_password(&pool, req.into_inner(), &config).await {
+        Ok(_) => {
+            HttpResponse::Ok().json(serde_json::json!({ "message": "Password reset successful" }))
+        }
+        Err(AppError::TooManyRequests(msg)) => {
+            HttpResponse::TooManyRequests().json(serde_json::json!({ "error": msg }))
+        }
+        Err(err) => {
+            HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }))
+        }
+    }
+}
+
+// 通过重置链接设置新密码
+pub async fn reset_password_with_token_handler(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    req: web::Json<ResetPasswordWithTokenRequest>,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = req.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+    
+    match reset_password_with_token(&pool, req.into_inner(), &config).await {
         Ok(_) => {
             HttpResponse::Ok().json(serde_json::json!({ "message": "Password reset successful" }))
         }
@@ -0,0 +1,47 @@
+use actix_web::{web, Responder, HttpResponse, HttpMessage};
+use crate::services::device::{get_user_devices, unbind_device};
+use crate::database::Pool;
+use crate::errors::AppError;
+
+// 获取当前用户绑定的设备列表
+pub async fn get_devices_handler(
+    pool: web::Data<Pool>,
+    req_ext: actix_web::HttpRequest,
+) -> impl Responder {
+    // 从请求扩展中获取用户ID
+    let user_id = if let Some(user_id) = req_ext.extensions().get::<i32>() {
+        *user_id
+    } else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" }));
+    };
+
+    match get_user_devices(&pool, user_id).await {
+        Ok(devices) => HttpResponse::Ok().json(devices),
+        Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
+
+// 解绑当前用户的指定设备
+pub async fn unbind_device_handler(
+    pool: web::Data<Pool>,
+    device_id: web::Path<i32>,
+    req_ext: actix_web::HttpRequest,
+) -> impl Responder {
+    // 从请求扩展中获取用户ID
+    let user_id = if let Some(user_id) = req_ext.extensions().get::<i32>() {
+        *user_id
+    } else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" }));
+    };
+
+    match unbind_device(&pool, user_id, device_id.into_inner()).await {
+        Ok((user, penalty_hours)) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "Device unbound",
+            "penalty_hours": penalty_hours,
+            "vip_expires_at": user.vip_expires_at,
+        })),
+        Err(AppError::NotFound(msg)) => HttpResponse::NotFound().json(serde_json::json!({ "error": msg })),
+        Err(AppError::TooManyRequests(msg)) => HttpResponse::TooManyRequests().json(serde_json::json!({ "error": msg })),
+        Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
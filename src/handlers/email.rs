@@ -1,6 +1,8 @@
 use actix_web::{web, HttpResponse, HttpRequest, HttpMessage, Responder};
 use serde::{Deserialize, Serialize};
+use validator::Validate;
 use crate::database::Pool;
+use crate::database::models::{ChangeEmailRequest, ConfirmEmailChangeRequest, CancelEmailChangeQuery};
 use crate::services::email::*;
 use crate::errors::AppError;
 use crate::config::Config;
@@ -12,6 +14,21 @@ pub struct VerifyEmailWithTokenRequest {
     pub code: String,
 }
 
+// 重新发送邮箱验证码请求DTO，激活令牌和邮箱二选一
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResendVerificationEmailRequest {
+    pub activation_token: Option<String>,
+    #[validate(email(message = "Invalid email format"))]
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ResendVerificationEmailResponse {
+    message: String,
+    activation_token: String,
+    retry_after_seconds: i64,
+}
+
 // 通过激活令牌验证邮箱
 pub async fn verify_email_with_token_handler(
     pool: web::Data<Pool>,
@@ -30,10 +47,13 @@ pub async fn verify_email_with_token_handler(
             };
             
             // 验证邮箱验证码
-            match verify_email_code(&pool, user_id, &req.code).await {
+            match verify_email_code(&pool, user_id, &req.code, &config).await {
                 Ok(_) => {
                     HttpResponse::Ok().json(serde_json::json!({ "message": "Email verified successfully" }))
                 },
+                Err(AppError::TooManyRequests(msg)) => {
+                    HttpResponse::TooManyRequests().json(serde_json::json!({ "error": msg }))
+                },
                 Err(err) => {
                     HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }))
                 }
@@ -43,4 +63,117 @@ pub async fn verify_email_with_token_handler(
             HttpResponse::Unauthorized().json(serde_json::json!({ "error": err.to_string() }))
         }
     }
-}
\ No newline at end of file
+}
+
+// 申请修改邮箱
+pub async fn request_email_change_handler(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    req: web::Json<ChangeEmailRequest>,
+    req_ext: HttpRequest,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = req.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+    
+    // 从请求扩展中获取用户ID
+    let user_id = if let Some(user_id) = req_ext.extensions().get::<i32>() {
+        *user_id
+    } else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" }));
+    };
+    
+    match request_email_change(&pool, user_id, req.into_inner(), &config).await {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({ "message": "Verification code sent to the new email address" })),
+        Err(AppError::Unauthorized(msg)) => HttpResponse::Unauthorized().json(serde_json::json!({ "error": msg })),
+        Err(AppError::TooManyRequests(msg)) => HttpResponse::TooManyRequests().json(serde_json::json!({ "error": msg })),
+        Err(AppError::BadRequest(msg)) => HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })),
+        Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
+
+// 确认修改邮箱
+pub async fn confirm_email_change_handler(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    req: web::Json<ConfirmEmailChangeRequest>,
+    req_ext: HttpRequest,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = req.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+    
+    // 从请求扩展中获取用户ID
+    let user_id = if let Some(user_id) = req_ext.extensions().get::<i32>() {
+        *user_id
+    } else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" }));
+    };
+    
+    match confirm_email_change(&pool, user_id, &req.code, &config).await {
+        Ok(user) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "Email changed successfully",
+            "email": user.email,
+            "email_verified": user.email_verified,
+        })),
+        Err(AppError::TooManyRequests(msg)) => HttpResponse::TooManyRequests().json(serde_json::json!({ "error": msg })),
+        Err(AppError::BadRequest(msg)) => HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })),
+        Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
+
+// 通过旧邮箱中的链接取消修改邮箱
+pub async fn cancel_email_change_handler(
+    pool: web::Data<Pool>,
+    query: web::Query<CancelEmailChangeQuery>,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = query.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+    
+    match cancel_email_change(&pool, &query.token).await {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({ "message": "Email change cancelled" })),
+        Err(AppError::BadRequest(msg)) => HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })),
+        Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
+
+// 重新发送邮箱验证码
+pub async fn resend_verification_email_handler(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    req: web::Json<ResendVerificationEmailRequest>,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = req.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+    
+    match resend_verification_email(&pool, req.activation_token.as_deref(), req.email.as_deref(), &config).await {
+        Ok(ResendOutcome::Sent(activation_token, retry_after_seconds)) => {
+            HttpResponse::Ok().json(ResendVerificationEmailResponse {
+                message: "Verification email sent".to_string(),
+                activation_token,
+                retry_after_seconds,
+            })
+        },
+        Ok(ResendOutcome::NotRegistered) => {
+            // 与已注册邮箱返回相同的状态码，不泄露邮箱是否已注册
+            HttpResponse::Ok().json(serde_json::json!({ "message": "If the email is registered, a verification email has been sent" }))
+        },
+        Ok(ResendOutcome::CoolingDown(retry_after_seconds)) => {
+            HttpResponse::TooManyRequests().json(serde_json::json!({
+                "error": "Please wait before requesting another verification email",
+                "retry_after_seconds": retry_after_seconds,
+            }))
+        },
+        Err(AppError::TooManyRequests(msg)) => HttpResponse::TooManyRequests().json(serde_json::json!({ "error": msg })),
+        Err(AppError::NotFound(_)) => HttpResponse::NotFound().json(serde_json::json!({ "error": "User not found" })),
+        Err(AppError::JwtError(msg)) => HttpResponse::Unauthorized().json(serde_json::json!({ "error": msg })),
+        Err(AppError::BadRequest(msg)) => HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })),
+        Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
@@ -1,21 +1,61 @@
 use actix_web::{web, Responder, HttpResponse};
+use serde::Serialize;
+use validator::Validate;
 use crate::database::models::*;
 use crate::services::heartbeat::*;
 use crate::database::Pool;
+use crate::config::Config;
+
+#[derive(Debug, Serialize)]
+struct HeartbeatResponse {
+    message: String,
+    status_interval: i32,
+    commands: Vec<CommandResponse>,
+}
+
+// 下发给客户端的会话指令，WebSocket推送时使用相同格式
+#[derive(Debug, Serialize)]
+pub struct CommandResponse {
+    id: i32,
+    command_type: String,
+    message: Option<String>,
+    payload: Option<serde_json::Value>,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<SessionCommand> for CommandResponse {
+    fn from(command: SessionCommand) -> Self {
+        Self {
+            id: command.id,
+            command_type: command.command_type,
+            message: command.message,
+            // payload 以JSON字符串存储，解析失败时原样作为字符串返回
+            payload: command.payload.map(|payload| {
+                serde_json::from_str(&payload).unwrap_or(serde_json::Value::String(payload))
+            }),
+            created_at: command.created_at,
+        }
+    }
+}
 
 // 上传心跳
 pub async fn heartbeat_handler(
     pool: web::Data<Pool>,
+    config: web::Data<Config>,
     req: web::Json<HeartbeatRequest>,
 ) -> impl Responder {
-    match update_heartbeat(
-        &pool, 
-        &req.session_token, 
-        &req.hardware_code, 
-        &req.software_version
-    ).await {
-        Ok(_) => {
-            HttpResponse::Ok().json(serde_json::json!({ "message": "Heartbeat updated successfully" }))
+    // 验证请求参数
+    if let Err(err) = req.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+    
+    match update_heartbeat(&pool, &req, &config).await {
+        Ok(outcome) => {
+            HttpResponse::Ok().json(HeartbeatResponse {
+                message: "Heartbeat updated successfully".to_string(),
+                status_interval: outcome.status_interval,
+                commands: outcome.commands.into_iter().map(CommandResponse::from).collect(),
+            })
         }
         Err(err) => {
             HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }))
@@ -0,0 +1,11 @@
+use actix_web::{web, Responder, HttpResponse};
+use crate::config::Config;
+
+// 获取用于离线验证令牌的公钥集合
+pub async fn jwks_handler(
+    config: web::Data<Config>,
+) -> impl Responder {
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "public, max-age=300"))
+        .json(&config.jwt_keys.jwks)
+}
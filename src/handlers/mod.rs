@@ -1,6 +1,12 @@
+pub mod activation;
 pub mod auth;
+pub mod device;
 pub mod email;
 pub mod heartbeat;
+pub mod jwks;
+pub mod push;
 pub mod recharge;
+pub mod session_command;
 pub mod software;
+pub mod two_factor;
 pub mod user;
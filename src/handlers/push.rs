@@ -0,0 +1,185 @@
+use std::time::{Duration, Instant};
+use actix_web::{web, HttpResponse, HttpMessage};
+use actix_ws::{CloseCode, CloseReason, Message};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use crate::database::models::*;
+use crate::handlers::heartbeat::CommandResponse;
+use crate::services::heartbeat::{end_session, touch_session};
+use crate::services::push::{PushMessage, PUSH_HUB};
+use crate::services::session_command::{COMMAND_FORCE_LOGOUT, acknowledge_session_commands, take_pending_session_commands};
+use crate::database::Pool;
+use crate::config::Config;
+
+// 服务器推送给客户端的消息
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    Command { command: CommandResponse },
+    SessionEnded { reason: String },
+}
+
+// 客户端发送给服务器的消息
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Ack { command_ids: Vec<i32> },
+}
+
+// 建立WebSocket推送连接
+pub async fn push_connection_handler(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    req: actix_web::HttpRequest,
+    body: web::Payload,
+) -> Result<HttpResponse, actix_web::Error> {
+    // 从请求扩展中获取当前会话
+    let online_user = if let Some(online_user) = req.extensions().get::<OnlineUser>() {
+        online_user.clone()
+    } else {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" })));
+    };
+    
+    let (response, ws_session, stream) = actix_ws::handle(&req, body)?;
+    
+    actix_web::rt::spawn(run_push_connection(pool, config, online_user, ws_session, stream));
+    
+    Ok(response)
+}
+
+// 推送连接主循环：转发推送消息、处理客户端确认，并在连接存活时刷新会话活动时间
+async fn run_push_connection(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    online_user: OnlineUser,
+    mut ws_session: actix_ws::Session,
+    mut stream: actix_ws::MessageStream,
+) {
+    let ping_interval = config.ws_ping_interval.max(Duration::from_secs(1));
+    let session_id = online_user.id;
+    let (connection_id, mut receiver) = PUSH_HUB.register(session_id);
+    
+    // 连接建立后先下发尚未确认的指令
+    let pending = pool.get()
+        .map_err(Into::into)
+        .and_then(|mut conn| take_pending_session_commands(&mut conn, session_id));
+    let mut close_reason = match pending {
+        Ok(commands) => {
+            let mut close_reason = None;
+            for command in commands {
+                close_reason = send_command(&pool, &mut ws_session, command).await;
+                if close_reason.is_some() {
+                    break;
+                }
+            }
+            close_reason
+        }
+        Err(err) => {
+            warn!("Failed to load pending commands for session {}: {}", session_id, err);
+            None
+        }
+    };
+    
+    let mut ticker = tokio::time::interval(ping_interval);
+    let mut last_seen = Instant::now();
+    
+    while close_reason.is_none() {
+        tokio::select! {
+            message = stream.recv() => match message {
+                Some(Ok(Message::Ping(bytes))) => {
+                    last_seen = Instant::now();
+                    if ws_session.pong(&bytes).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Text(text))) => {
+                    last_seen = Instant::now();
+                    handle_client_message(&pool, session_id, &text);
+                }
+                Some(Ok(Message::Close(reason))) => {
+                    close_reason = reason;
+                    break;
+                }
+                Some(Ok(_)) => last_seen = Instant::now(),
+                Some(Err(_)) | None => break,
+            },
+            push = receiver.recv() => match push {
+                Some(PushMessage::Command(command)) => {
+                    close_reason = send_command(&pool, &mut ws_session, command).await;
+                }
+                Some(PushMessage::SessionEnded(reason)) => {
+                    // 会话已被登出、踢下线、超时清理或封禁，告知原因后断开
+                    if let Ok(text) = serde_json::to_string(&ServerMessage::SessionEnded { reason: reason.clone() }) {
+                        let _ = ws_session.text(text).await;
+                    }
+                    close_reason = Some(CloseReason { code: CloseCode::Policy, description: Some(format!("Session has ended: {}", reason)) });
+                }
+                None => break,
+            },
+            _ = ticker.tick() => {
+                // 超过3个间隔未收到客户端消息时断开
+                if last_seen.elapsed() > ping_interval * 3 {
+                    close_reason = Some(CloseReason { code: CloseCode::Away, description: Some("Connection timed out".to_string()) });
+                    break;
+                }
+                
+                // 连接存活时刷新会话活动时间，会话已被踢下线或登出时断开
+                match touch_session(&pool, session_id, &config).await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        close_reason = Some(CloseReason { code: CloseCode::Policy, description: Some("Session has ended".to_string()) });
+                        break;
+                    }
+                    Err(err) => warn!("Failed to refresh activity for session {}: {}", session_id, err),
+                }
+                
+                if ws_session.ping(b"").await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    
+    PUSH_HUB.unregister(session_id, connection_id);
+    let _ = ws_session.close(close_reason).await;
+}
+
+// 向客户端发送一条指令，强制下线指令发送后结束会话并返回关闭原因
+async fn send_command(pool: &Pool, ws_session: &mut actix_ws::Session, command: SessionCommand) -> Option<CloseReason> {
+    let session_id = command.session_id;
+    let force_logout = command.command_type == COMMAND_FORCE_LOGOUT;
+    let message = ServerMessage::Command { command: CommandResponse::from(command) };
+    
+    let sent = match serde_json::to_string(&message) {
+        Ok(text) => ws_session.text(text).await.is_ok(),
+        Err(_) => false,
+    };
+    
+    if force_logout {
+        if let Err(err) = end_session(pool, session_id).await {
+            warn!("Failed to end session {} after force logout: {}", session_id, err);
+        }
+        return Some(CloseReason { code: CloseCode::Policy, description: Some("Logged out by server".to_string()) });
+    }
+    
+    if !sent {
+        return Some(CloseReason { code: CloseCode::Error, description: None });
+    }
+    
+    None
+}
+
+// 处理客户端消息，目前只有指令确认
+fn handle_client_message(pool: &Pool, session_id: i32, text: &str) {
+    let command_ids = match serde_json::from_str::<ClientMessage>(text) {
+        Ok(ClientMessage::Ack { command_ids }) => command_ids,
+        Err(_) => return,
+    };
+    
+    let result = pool.get()
+        .map_err(Into::into)
+        .and_then(|mut conn| acknowledge_session_commands(&mut conn, session_id, &command_ids));
+    if let Err(err) = result {
+        warn!("Failed to acknowledge commands for session {}: {}", session_id, err);
+    }
+}
@@ -1,8 +1,13 @@
 use actix_web::{web, Responder, HttpResponse, HttpRequest, HttpMessage};
 use serde::{Deserialize, Serialize};
+use log::warn;
 use crate::database::models::*;
 use crate::services::recharge::*;
+use crate::services::auth::check_email_verified;
+use crate::services::session_command::notify_entitlement_changed;
 use crate::database::Pool;
+use crate::config::{Config, VERIFIED_EMAIL_ACTION_RECHARGE};
+use crate::errors::{AppError, email_not_verified_response};
 
 #[derive(Debug, Serialize)]
 struct RechargeResponse {
@@ -15,6 +20,7 @@ struct RechargeResponse {
 // 卡密充值
 pub async fn recharge_handler(
     pool: web::Data<Pool>,
+    config: web::Data<Config>,
     req: web::Json<RechargeRequest>,
     req_ext: actix_web::HttpRequest,
 ) -> impl Responder {
@@ -25,8 +31,20 @@ pub async fn recharge_handler(
         return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" }));
     };
     
+    // 按配置检查邮箱是否已验证
+    match check_email_verified(&pool, user_id, VERIFIED_EMAIL_ACTION_RECHARGE, &config).await {
+        Ok(_) => {}
+        Err(AppError::EmailNotVerified(msg)) => return email_not_verified_response(&msg),
+        Err(err) => return HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() })),
+    }
+    
     match recharge_with_card(&pool, user_id, &req.card_code).await {
         Ok((user, recharge_log)) => {
+            // 通知该用户的在线客户端权益已变更
+            if let Err(err) = notify_entitlement_changed(&pool, user_id).await {
+                warn!("Failed to notify entitlement change for user {}: {}", user_id, err);
+            }
+            
             HttpResponse::Ok().json(RechargeResponse {
                 message: "Recharge successful".to_string(),
                 vip_level: user.vip_level,
@@ -0,0 +1,47 @@
+use actix_web::{web, Responder, HttpResponse};
+use validator::Validate;
+use crate::database::models::{BroadcastSessionCommandRequest, EnqueueSessionCommandRequest};
+use crate::services::session_command::{broadcast_session_command, enqueue_session_commands};
+use crate::database::Pool;
+use crate::errors::AppError;
+
+// 管理员向用户的在线会话下发指令
+pub async fn enqueue_session_command_handler(
+    pool: web::Data<Pool>,
+    req: web::Json<EnqueueSessionCommandRequest>,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = req.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+    
+    match enqueue_session_commands(&pool, req.into_inner()).await {
+        Ok(commands) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "Command queued",
+            "command_ids": commands.iter().map(|command| command.id).collect::<Vec<i32>>(),
+        })),
+        Err(AppError::BadRequest(msg)) => HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })),
+        Err(AppError::NotFound(msg)) => HttpResponse::NotFound().json(serde_json::json!({ "error": msg })),
+        Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
+
+// 管理员向所有在线会话广播指令（公告、更新提醒）
+pub async fn broadcast_session_command_handler(
+    pool: web::Data<Pool>,
+    req: web::Json<BroadcastSessionCommandRequest>,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = req.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+    
+    match broadcast_session_command(&pool, req.into_inner()).await {
+        Ok(count) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "Command broadcast",
+            "count": count,
+        })),
+        Err(AppError::BadRequest(msg)) => HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })),
+        Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
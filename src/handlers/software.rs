@@ -1,7 +1,19 @@
 use actix_web::{web, Responder, HttpResponse, HttpMessage};
+use serde::Serialize;
 use crate::database::models::*;
 use crate::services::software::*;
+use crate::services::license::issue_license;
+use crate::services::auth::check_email_verified;
 use crate::database::Pool;
+use crate::config::{Config, VERIFIED_EMAIL_ACTION_SOFTWARE_ACCESS};
+use crate::errors::{AppError, email_not_verified_response};
+
+#[derive(Debug, Serialize)]
+struct LicenseResponse {
+    license: String,
+    expires_at: chrono::DateTime<chrono::Utc>,
+    offline_grace_seconds: u64,
+}
 
 // 获取所有软件列表
 pub async fn get_all_software_handler(
@@ -16,6 +28,7 @@ pub async fn get_all_software_handler(
 // 检查软件访问权限
 pub async fn check_software_access_handler(
     pool: web::Data<Pool>,
+    config: web::Data<Config>,
     software_id: web::Path<i32>,
     req_ext: actix_web::HttpRequest,
 ) -> impl Responder {
@@ -26,8 +39,65 @@ pub async fn check_software_access_handler(
         return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" }));
     };
     
+    // 按配置检查邮箱是否已验证
+    match check_email_verified(&pool, user_id, VERIFIED_EMAIL_ACTION_SOFTWARE_ACCESS, &config).await {
+        Ok(_) => {}
+        Err(AppError::EmailNotVerified(msg)) => return email_not_verified_response(&msg),
+        Err(err) => return HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() })),
+    }
+    
     match check_software_access(&pool, user_id, software_id.into_inner()).await {
         Ok(has_access) => HttpResponse::Ok().json(serde_json::json!({ "has_access": has_access })),
         Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
     }
 }
+
+// 签发离线许可证
+pub async fn issue_license_handler(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    software_id: web::Path<i32>,
+    req_ext: actix_web::HttpRequest,
+) -> impl Responder {
+    // 从请求扩展中获取当前会话
+    let session = if let Some(session) = req_ext.extensions().get::<OnlineUser>() {
+        session.clone()
+    } else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" }));
+    };
+    
+    // 按配置检查邮箱是否已验证
+    match check_email_verified(&pool, session.user_id, VERIFIED_EMAIL_ACTION_SOFTWARE_ACCESS, &config).await {
+        Ok(_) => {}
+        Err(AppError::EmailNotVerified(msg)) => return email_not_verified_response(&msg),
+        Err(err) => return HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() })),
+    }
+    
+    match issue_license(&pool, &session, software_id.into_inner(), &config).await {
+        Ok((license, expires_at)) => HttpResponse::Ok().json(LicenseResponse {
+            license,
+            expires_at,
+            offline_grace_seconds: config.license_offline_grace.as_secs(),
+        }),
+        Err(AppError::Forbidden(msg)) => HttpResponse::Forbidden().json(serde_json::json!({ "error": msg })),
+        Err(AppError::NotFound(msg)) => HttpResponse::NotFound().json(serde_json::json!({ "error": msg })),
+        Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
+
+// 管理员为软件生成新的客户端密钥
+pub async fn rotate_client_secret_handler(
+    pool: web::Data<Pool>,
+    software_id: web::Path<i32>,
+) -> impl Responder {
+    let software_id = software_id.into_inner();
+    
+    match rotate_client_secret(&pool, software_id).await {
+        Ok(Some(client_secret)) => HttpResponse::Ok().json(serde_json::json!({
+            "software_id": software_id,
+            "client_secret": client_secret,
+        })),
+        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({ "error": "Software not found" })),
+        Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
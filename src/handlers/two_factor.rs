@@ -0,0 +1,89 @@
+use actix_web::{web, Responder, HttpResponse, HttpMessage};
+use serde::Serialize;
+use validator::Validate;
+use crate::database::models::*;
+use crate::services::two_factor::*;
+use crate::database::Pool;
+use crate::config::Config;
+
+#[derive(Debug, Serialize)]
+struct EnrollTwoFactorResponse {
+    secret: String,
+    otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ConfirmTwoFactorResponse {
+    message: String,
+    backup_codes: Vec<String>,
+}
+
+// 开始绑定两步验证
+pub async fn enroll_two_factor_handler(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    req_ext: actix_web::HttpRequest,
+) -> impl Responder {
+    // 从请求扩展中获取用户ID
+    let user_id = if let Some(user_id) = req_ext.extensions().get::<i32>() {
+        *user_id
+    } else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" }));
+    };
+    
+    match enroll_two_factor(&pool, user_id, &config).await {
+        Ok((secret, otpauth_uri)) => HttpResponse::Ok().json(EnrollTwoFactorResponse { secret, otpauth_uri }),
+        Err(err) => HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
+
+// 确认绑定并启用两步验证
+pub async fn confirm_two_factor_handler(
+    pool: web::Data<Pool>,
+    req: web::Json<TwoFactorCodeRequest>,
+    req_ext: actix_web::HttpRequest,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = req.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+    
+    // 从请求扩展中获取用户ID
+    let user_id = if let Some(user_id) = req_ext.extensions().get::<i32>() {
+        *user_id
+    } else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" }));
+    };
+    
+    match confirm_two_factor(&pool, user_id, &req.code).await {
+        Ok(backup_codes) => HttpResponse::Ok().json(ConfirmTwoFactorResponse {
+            message: "Two-factor authentication enabled".to_string(),
+            backup_codes,
+        }),
+        Err(err) => HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
+
+// 关闭两步验证
+pub async fn disable_two_factor_handler(
+    pool: web::Data<Pool>,
+    req: web::Json<DisableTwoFactorRequest>,
+    req_ext: actix_web::HttpRequest,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = req.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+    
+    // 从请求扩展中获取用户ID
+    let user_id = if let Some(user_id) = req_ext.extensions().get::<i32>() {
+        *user_id
+    } else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" }));
+    };
+    
+    match disable_two_factor(&pool, user_id, &req.password, &req.code).await {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({ "message": "Two-factor authentication disabled" })),
+        Err(err) => HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
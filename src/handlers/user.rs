@@ -1,8 +1,13 @@
 use actix_web::{web, Responder, HttpResponse, HttpMessage};
 use serde::{Deserialize, Serialize};
+use validator::Validate;
 use crate::database::models::*;
 use crate::services::user::*;
+use crate::services::auth::{change_password, logout_all_sessions, revoke_user_session};
+use crate::services::session_history::get_session_history;
 use crate::database::Pool;
+use crate::config::Config;
+use crate::errors::AppError;
 
 // 软件列表响应结构体，包含软件列表和用户VIP信息
 #[derive(Debug, Serialize)]
@@ -12,6 +17,36 @@ struct SoftwareListResponse {
     software_list: Vec<Software>,
 }
 
+// 在线会话信息，不包含会话令牌
+#[derive(Debug, Serialize)]
+struct SessionInfo {
+    id: i32,
+    hardware_code: String,
+    software_version: String,
+    ip_address: String,
+    login_time: chrono::DateTime<chrono::Utc>,
+    last_activity_at: chrono::DateTime<chrono::Utc>,
+    current: bool,
+}
+
+// 登录历史分页响应
+#[derive(Debug, Serialize)]
+struct LoginHistoryResponse {
+    page: i64,
+    page_size: i64,
+    total: i64,
+    logs: Vec<LoginLog>,
+}
+
+// 会话历史分页响应
+#[derive(Debug, Serialize)]
+struct SessionHistoryResponse {
+    page: i64,
+    page_size: i64,
+    total: i64,
+    sessions: Vec<SessionHistory>,
+}
+
 // 获取当前用户信息
 pub async fn get_user_info_handler(
     pool: web::Data<Pool>,
@@ -53,3 +88,185 @@ pub async fn get_available_software_handler(
         Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
     }
 }
+
+// 修改密码
+pub async fn change_password_handler(
+    pool: web::Data<Pool>,
+    config: web::Data<Config>,
+    req: web::Json<ChangePasswordRequest>,
+    req_ext: actix_web::HttpRequest,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = req.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+    
+    // 从请求扩展中获取当前会话
+    let session = if let Some(session) = req_ext.extensions().get::<OnlineUser>() {
+        session.clone()
+    } else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" }));
+    };
+    
+    match change_password(&pool, &session, req.into_inner(), &config).await {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({ "message": "Password changed successfully, other sessions have been logged out" })),
+        Err(AppError::BadRequest(msg)) => HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })),
+        Err(AppError::Unauthorized(msg)) => HttpResponse::Unauthorized().json(serde_json::json!({ "error": msg })),
+        Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
+
+// 退出所有设备
+pub async fn logout_all_handler(
+    pool: web::Data<Pool>,
+    req_ext: actix_web::HttpRequest,
+) -> impl Responder {
+    // 从请求扩展中获取用户ID
+    let user_id = if let Some(user_id) = req_ext.extensions().get::<i32>() {
+        *user_id
+    } else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" }));
+    };
+    
+    match logout_all_sessions(&pool, user_id).await {
+        Ok(logged_out_sessions) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "Logged out from all devices",
+            "logged_out_sessions": logged_out_sessions,
+        })),
+        Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
+
+// 获取当前用户的在线会话列表
+pub async fn get_sessions_handler(
+    pool: web::Data<Pool>,
+    req_ext: actix_web::HttpRequest,
+) -> impl Responder {
+    // 从请求扩展中获取当前会话
+    let current = if let Some(session) = req_ext.extensions().get::<OnlineUser>() {
+        session.clone()
+    } else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" }));
+    };
+    
+    match get_user_sessions(&pool, current.user_id).await {
+        Ok(sessions) => {
+            let sessions: Vec<SessionInfo> = sessions.into_iter()
+                .map(|session| SessionInfo {
+                    current: session.id == current.id,
+                    id: session.id,
+                    hardware_code: session.hardware_code,
+                    software_version: session.software_version,
+                    ip_address: session.ip_address,
+                    login_time: session.login_time,
+                    last_activity_at: session.last_activity_at,
+                })
+                .collect();
+            HttpResponse::Ok().json(sessions)
+        }
+        Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
+
+// 结束当前用户的指定会话
+pub async fn revoke_session_handler(
+    pool: web::Data<Pool>,
+    session_id: web::Path<i32>,
+    req_ext: actix_web::HttpRequest,
+) -> impl Responder {
+    // 从请求扩展中获取用户ID
+    let user_id = if let Some(user_id) = req_ext.extensions().get::<i32>() {
+        *user_id
+    } else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" }));
+    };
+    
+    match revoke_user_session(&pool, user_id, session_id.into_inner()).await {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({ "message": "Session revoked" })),
+        Err(AppError::NotFound(msg)) => HttpResponse::NotFound().json(serde_json::json!({ "error": msg })),
+        Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
+
+// 分页获取当前用户的登录历史
+pub async fn get_login_history_handler(
+    pool: web::Data<Pool>,
+    query: web::Query<LoginHistoryQuery>,
+    req_ext: actix_web::HttpRequest,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = query.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+    
+    // 从请求扩展中获取用户ID
+    let user_id = if let Some(user_id) = req_ext.extensions().get::<i32>() {
+        *user_id
+    } else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" }));
+    };
+    
+    let page = query.page.unwrap_or(1);
+    let page_size = query.page_size.unwrap_or(20);
+    
+    match get_login_history(&pool, user_id, page, page_size).await {
+        Ok((total, logs)) => HttpResponse::Ok().json(LoginHistoryResponse {
+            page,
+            page_size,
+            total,
+            logs,
+        }),
+        Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
+
+// 获取当前用户的会话历史
+pub async fn get_session_history_handler(
+    pool: web::Data<Pool>,
+    query: web::Query<LoginHistoryQuery>,
+    req_ext: actix_web::HttpRequest,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = query.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+    
+    // 从请求扩展中获取用户ID
+    let user_id = if let Some(user_id) = req_ext.extensions().get::<i32>() {
+        *user_id
+    } else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" }));
+    };
+    
+    session_history_response(&pool, user_id, &query).await
+}
+
+// 管理员查询指定用户的会话历史
+pub async fn admin_get_session_history_handler(
+    pool: web::Data<Pool>,
+    path: web::Path<i32>,
+    query: web::Query<LoginHistoryQuery>,
+) -> impl Responder {
+    // 验证请求参数
+    if let Err(err) = query.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": err.to_string() }));
+    }
+    
+    session_history_response(&pool, path.into_inner(), &query).await
+}
+
+// 分页查询会话历史并生成响应
+async fn session_history_response(pool: &Pool, user_id: i32, query: &LoginHistoryQuery) -> HttpResponse {
+    let page = query.page.unwrap_or(1);
+    let page_size = query.page_size.unwrap_or(20);
+    
+    match get_session_history(pool, user_id, page, page_size).await {
+        Ok((total, sessions)) => HttpResponse::Ok().json(SessionHistoryResponse {
+            page,
+            page_size,
+            total,
+            sessions,
+        }),
+        Err(err) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": err.to_string() }))
+    }
+}
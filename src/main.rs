@@ -6,8 +6,10 @@ use log::{error, info};
 
 use crate::database::create_pool;
 use crate::routes::configure_routes;
-use crate::background::start_cleanup_task;
+use crate::services::heartbeat::flush_heartbeat_buffer;
+use crate::background::{start_cleanup_task, start_heartbeat_flush_task};
 use crate::utils::logger::init_logger;
+use crate::utils::nonce_store::NonceStore;
 use crate::config::Config;
 
 // 嵌入数据库迁移文件
@@ -37,7 +39,6 @@ async fn main() -> std::io::Result<()> {
     
     // 获取配置
     let config = Config::new();
-    let cleanup_interval = config.cleanup_interval.as_secs() / 60; // 转换为分钟
     
     // 创建数据库连接池
     let pool = create_pool(&config);
@@ -59,8 +60,13 @@ async fn main() -> std::io::Result<()> {
     info!("Database migrations completed");
     
     // 启动后台清理任务
-    info!("Starting background cleanup task with interval {} minutes", cleanup_interval);
-    tokio::spawn(start_cleanup_task(pool.clone(), cleanup_interval));
+    info!("Starting background cleanup task with interval {} seconds", config.cleanup_interval.as_secs());
+    tokio::spawn(start_cleanup_task(pool.clone(), config.cleanup_interval, config.heartbeat_timeout_multiplier));
+    
+    // 启动心跳写回任务
+    tokio::spawn(start_heartbeat_flush_task(pool.clone(), config.heartbeat_flush_interval));
+    // 服务停止后写回缓冲中剩余的心跳
+    let flush_pool = pool.clone();
     
     // 配置API速率限制
     let governor_config = GovernorConfigBuilder::default()
@@ -70,6 +76,8 @@ async fn main() -> std::io::Result<()> {
         .unwrap();
     
     let governor_config = web::Data::new(governor_config);
+    // 请求签名的nonce存储，所有工作线程共享
+    let nonce_store = web::Data::new(NonceStore::default());
     let config_clone = config.clone();
     let server_app = move || {
         App::new()
@@ -81,6 +89,8 @@ async fn main() -> std::io::Result<()> {
             .app_data(web::Data::new(pool.clone()))
             // 注册配置
             .app_data(web::Data::new(config_clone.clone()))
+            // 注册请求签名的nonce存储
+            .app_data(nonce_store.clone())
             // 配置路由
             .configure(configure_routes)
     };
@@ -114,5 +124,13 @@ async fn main() -> std::io::Result<()> {
     }
     
     // 只运行HTTP服务器
-    http_server.run().await
+    let result = http_server.run().await;
+    
+    info!("Flushing buffered heartbeats before shutdown");
+    match flush_heartbeat_buffer(&flush_pool).await {
+        Ok(flushed) => info!("Flushed {} buffered heartbeats", flushed),
+        Err(err) => error!("Failed to flush buffered heartbeats: {}", err),
+    }
+    
+    result
 }
@@ -0,0 +1,42 @@
+use actix_web::{dev::ServiceRequest, dev::ServiceResponse, Error, web};
+use actix_web::middleware::Next;
+use actix_web::body::BoxBody;
+use crate::utils::crypto::constant_time_eq;
+use crate::config::Config;
+
+// 管理接口认证中间件
+pub async fn admin_middleware(
+    req: ServiceRequest,
+    next: Next<BoxBody>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    // 获取配置
+    let config = match req.app_data::<web::Data<Config>>() {
+        Some(config) => config,
+        None => {
+            let response = actix_web::HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": "Config not found" }));
+            return Ok(req.into_response(response));
+        }
+    };
+    
+    // 未配置管理密钥时禁用管理接口
+    if config.admin_api_key.is_empty() {
+        let response = actix_web::HttpResponse::Forbidden()
+            .json(serde_json::json!({ "error": "Admin API is disabled" }));
+        return Ok(req.into_response(response));
+    }
+    
+    // 从请求头获取管理密钥
+    let is_authorized = req.headers()
+        .get("X-Admin-Key")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|key| constant_time_eq(key, &config.admin_api_key));
+    
+    if !is_authorized {
+        let response = actix_web::HttpResponse::Unauthorized()
+            .json(serde_json::json!({ "error": "Invalid admin key" }));
+        return Ok(req.into_response(response));
+    }
+    
+    next.call(req).await
+}
@@ -2,6 +2,8 @@ use actix_web::{dev::ServiceRequest, dev::ServiceResponse, Error, HttpMessage, w
 use actix_web::middleware::Next;
 use actix_web::body::BoxBody;
 use crate::utils::jwt::verify_token;
+use crate::services::auth::validate_session;
+use crate::database::Pool;
 use crate::config::Config;
 
 // 认证中间件
@@ -19,26 +21,44 @@ pub async fn auth_middleware(
         }
     };
     
+    // 获取数据库连接池
+    let pool = match req.app_data::<web::Data<Pool>>() {
+        Some(pool) => pool.clone(),
+        None => {
+            let response = actix_web::HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": "Database pool not found" }));
+            return Ok(req.into_response(response));
+        }
+    };
+    
     // 从请求头获取token
     let auth_header = req.headers().get(actix_web::http::header::AUTHORIZATION);
     
     if let Some(auth_value) = auth_header {
         if let Ok(auth_str) = auth_value.to_str() {
             if auth_str.starts_with("Bearer ") {
-                let token = auth_str.trim_start_matches("Bearer ");
+                let token = auth_str.trim_start_matches("Bearer ").to_string();
+                
+                // 验证token签名和有效期
+                if verify_token(&token, config).is_err() {
+                    // Token无效
+                    let response = actix_web::HttpResponse::Unauthorized()
+                        .json(serde_json::json!({ "error": "Invalid token" }));
+                    return Ok(req.into_response(response));
+                }
                 
-                // 验证token
-                match verify_token(token, config) {
-                    Ok(claims) => {
-                        // 将用户ID存储到请求扩展中
-                        let user_id = claims.sub.parse::<i32>().unwrap_or(0);
-                        req.extensions_mut().insert(user_id);
+                // 验证token仍对应有效的服务端会话
+                match validate_session(&pool, &token, config).await {
+                    Ok(online_user) => {
+                        // 将用户ID和当前会话存储到请求扩展中
+                        req.extensions_mut().insert(online_user.user_id);
+                        req.extensions_mut().insert(online_user);
                         return next.call(req).await;
                     }
-                    Err(_) => {
-                        // Token无效
+                    Err(err) => {
+                        // 会话已失效
                         let response = actix_web::HttpResponse::Unauthorized()
-                            .json(serde_json::json!({ "error": "Invalid token" }));
+                            .json(serde_json::json!({ "error": err.to_string() }));
                         return Ok(req.into_response(response));
                     }
                 }
@@ -0,0 +1,113 @@
+use actix_web::{dev::ServiceRequest, dev::ServiceResponse, dev::Payload, Error, web};
+use actix_web::middleware::Next;
+use actix_web::body::BoxBody;
+use chrono::Utc;
+use crate::utils::crypto::{constant_time_eq, hmac_sha256_hex, sha256_hex_bytes};
+use crate::utils::nonce_store::NonceStore;
+use crate::services::software::get_client_secret;
+use crate::database::Pool;
+use crate::config::Config;
+
+// 请求签名校验中间件
+//
+// 客户端使用软件的客户端密钥对以下内容（以换行符连接）计算HMAC-SHA256，放入 X-Signature 请求头：
+// 请求方法、请求路径（含查询参数）、X-Timestamp、X-Nonce、请求体的SHA-256十六进制摘要。
+// 未启用请求签名时直接放行。
+pub async fn signature_middleware(
+    mut req: ServiceRequest,
+    next: Next<BoxBody>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    // 获取配置
+    let config = match req.app_data::<web::Data<Config>>() {
+        Some(config) => config.clone(),
+        None => {
+            let response = actix_web::HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": "Config not found" }));
+            return Ok(req.into_response(response));
+        }
+    };
+    
+    if !config.request_signing_enabled {
+        return next.call(req).await;
+    }
+    
+    // 获取数据库连接池和nonce存储
+    let (pool, nonce_store) = match (req.app_data::<web::Data<Pool>>(), req.app_data::<web::Data<NonceStore>>()) {
+        (Some(pool), Some(nonce_store)) => (pool.clone(), nonce_store.clone()),
+        _ => {
+            let response = actix_web::HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": "Signature verification is not configured" }));
+            return Ok(req.into_response(response));
+        }
+    };
+    
+    // 读取签名相关请求头
+    let header = |name: &str| req.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+    let (software_id, timestamp, nonce, signature) = match (
+        header("X-Software-Id").and_then(|value| value.parse::<i32>().ok()),
+        header("X-Timestamp").and_then(|value| value.parse::<i64>().ok()),
+        header("X-Nonce").filter(|value| (8..=64).contains(&value.len())),
+        header("X-Signature"),
+    ) {
+        (Some(software_id), Some(timestamp), Some(nonce), Some(signature)) => (software_id, timestamp, nonce, signature),
+        _ => {
+            let response = actix_web::HttpResponse::Unauthorized()
+                .json(serde_json::json!({ "error": "Missing or invalid signature headers" }));
+            return Ok(req.into_response(response));
+        }
+    };
+    
+    // 检查时间戳是否在允许的时钟偏差范围内
+    // 时间戳来自未认证的请求头，用 abs_diff 避免极端值溢出
+    let max_skew = config.request_signing_max_skew.as_secs();
+    if Utc::now().timestamp().abs_diff(timestamp) > max_skew {
+        let response = actix_web::HttpResponse::Unauthorized()
+            .json(serde_json::json!({ "error": "Request timestamp is outside the allowed window" }));
+        return Ok(req.into_response(response));
+    }
+    
+    // 获取软件的客户端密钥
+    let client_secret = match get_client_secret(&pool, software_id).await {
+        Ok(Some(client_secret)) => client_secret,
+        Ok(None) => {
+            let response = actix_web::HttpResponse::Unauthorized()
+                .json(serde_json::json!({ "error": "Unknown software" }));
+            return Ok(req.into_response(response));
+        }
+        Err(err) => {
+            let response = actix_web::HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": err.to_string() }));
+            return Ok(req.into_response(response));
+        }
+    };
+    
+    // 读取请求体后放回，供后续处理器使用
+    let body = req.extract::<web::Bytes>().await?;
+    req.set_payload(Payload::from(body.clone()));
+    
+    let path = req.uri().path_and_query().map(|path| path.as_str()).unwrap_or_else(|| req.path());
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}",
+        req.method().as_str(),
+        path,
+        timestamp,
+        nonce,
+        sha256_hex_bytes(&body)
+    );
+    
+    if !constant_time_eq(&hmac_sha256_hex(&client_secret, &canonical_request), &signature.to_lowercase()) {
+        let response = actix_web::HttpResponse::Unauthorized()
+            .json(serde_json::json!({ "error": "Invalid signature" }));
+        return Ok(req.into_response(response));
+    }
+    
+    // 签名有效后再记录nonce，时间窗口两侧都需要覆盖
+    let nonce_key = format!("{}:{}", software_id, nonce);
+    if !nonce_store.insert(&nonce_key, config.request_signing_max_skew * 2) {
+        let response = actix_web::HttpResponse::Unauthorized()
+            .json(serde_json::json!({ "error": "Nonce has already been used" }));
+        return Ok(req.into_response(response));
+    }
+    
+    next.call(req).await
+}
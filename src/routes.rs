@@ -1,6 +1,8 @@
 use actix_web::{web, App};
 use crate::handlers::*;
 use crate::middleware::auth::auth_middleware;
+use crate::middleware::admin::admin_middleware;
+use crate::middleware::signature::signature_middleware;
 
 // 配置路由
 pub fn configure_routes(cfg: &mut web::ServiceConfig) {
@@ -9,19 +11,41 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
                 web::scope("/api")
                     // 认证相关路由
                     .service(web::resource("/auth/register").route(web::post().to(auth::register_handler)))
-                    .service(web::resource("/auth/login").route(web::post().to(auth::login_handler)))
+                    .service(web::resource("/auth/login").wrap(actix_web::middleware::from_fn(signature_middleware)).route(web::post().to(auth::login_handler)))
                     .service(web::resource("/auth/refresh").route(web::post().to(auth::refresh_token_handler)))
+                    .service(web::resource("/auth/2fa/verify").wrap(actix_web::middleware::from_fn(signature_middleware)).route(web::post().to(auth::verify_two_factor_login_handler)))
+                    .service(web::resource("/auth/magic-link").route(web::post().to(auth::request_magic_link_handler)))
+                    .service(web::resource("/auth/magic-link/verify").wrap(actix_web::middleware::from_fn(signature_middleware)).route(web::post().to(auth::redeem_magic_link_handler)))
                     .service(web::resource("/auth/reset-password").route(web::post().to(auth::reset_password_handler)))
                     .service(web::resource("/auth/reset-password/verify").route(web::post().to(auth::verify_reset_password_handler)))
+                    .service(web::resource("/auth/reset-password/token").route(web::post().to(auth::reset_password_with_token_handler)))
                     
                     // 退出登录路由 - 无需认证，因为目的是使token失效
                     .service(web::resource("/auth/logout").route(web::post().to(auth::logout_handler)))
                     
                     // 邮箱验证路由 - 无需认证，使用激活令牌
                     .service(web::resource("/auth/verify-email").route(web::post().to(email::verify_email_with_token_handler)))
+                    .service(web::resource("/auth/verify-email/resend").route(web::post().to(email::resend_verification_email_handler)))
                     
-                    // 心跳路由
-                    .service(web::resource("/heartbeat").route(web::post().to(heartbeat::heartbeat_handler)))
+                    // 取消邮箱变更路由 - 无需认证，使用旧邮箱收到的取消令牌
+                    .service(web::resource("/auth/email-change/cancel").route(web::get().to(email::cancel_email_change_handler)))
+                    
+                    // 公钥集合路由 - 供客户端和合作服务离线验证令牌
+                    .service(web::resource("/.well-known/jwks.json").route(web::get().to(jwks::jwks_handler)))
+                    
+                    // 心跳路由 - 启用请求签名时校验签名
+                    .service(web::resource("/heartbeat").wrap(actix_web::middleware::from_fn(signature_middleware)).route(web::post().to(heartbeat::heartbeat_handler)))
+                    
+                    // 管理接口 - 使用管理密钥认证
+                    .service(
+                        web::scope("/admin")
+                            .wrap(actix_web::middleware::from_fn(admin_middleware))
+                            .service(web::resource("/offline-activations").route(web::post().to(activation::admin_offline_activation_handler)))
+                            .service(web::resource("/session-commands").route(web::post().to(session_command::enqueue_session_command_handler)))
+                            .service(web::resource("/session-commands/broadcast").route(web::post().to(session_command::broadcast_session_command_handler)))
+                            .service(web::resource("/software/{software_id}/client-secret").route(web::post().to(software::rotate_client_secret_handler)))
+                            .service(web::resource("/users/{user_id}/session-history").route(web::get().to(user::admin_get_session_history_handler)))
+                    )
             
             // 需要认证的路由
             .service(
@@ -31,16 +55,38 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
                     // 用户相关路由
                     .service(web::resource("/users/me").route(web::get().to(user::get_user_info_handler)))
                     .service(web::resource("/users/software").route(web::get().to(user::get_available_software_handler)))
+                    .service(web::resource("/users/me/password").route(web::post().to(user::change_password_handler)))
+                    .service(web::resource("/users/me/logout-all").route(web::post().to(user::logout_all_handler)))
+                    .service(web::resource("/users/me/sessions").route(web::get().to(user::get_sessions_handler)))
+                    .service(web::resource("/users/me/sessions/{session_id}").route(web::delete().to(user::revoke_session_handler)))
+                    .service(web::resource("/users/me/devices").route(web::get().to(device::get_devices_handler)))
+                    .service(web::resource("/users/me/devices/{device_id}").route(web::delete().to(device::unbind_device_handler)))
+                    .service(web::resource("/users/me/login-history").route(web::get().to(user::get_login_history_handler)))
+                    .service(web::resource("/users/me/session-history").route(web::get().to(user::get_session_history_handler)))
+                    .service(web::resource("/users/me/email").route(web::post().to(email::request_email_change_handler)))
+                    .service(web::resource("/users/me/email/confirm").route(web::post().to(email::confirm_email_change_handler)))
+                    
+                    // 两步验证相关路由
+                    .service(web::resource("/users/me/2fa/enroll").route(web::post().to(two_factor::enroll_two_factor_handler)))
+                    .service(web::resource("/users/me/2fa/confirm").route(web::post().to(two_factor::confirm_two_factor_handler)))
+                    .service(web::resource("/users/me/2fa/disable").route(web::post().to(two_factor::disable_two_factor_handler)))
                     
                     // 邮箱验证相关路由已删除
                     
                     // 充值相关路由
-                    .service(web::resource("/recharge").route(web::post().to(recharge::recharge_handler)))
+                    .service(web::resource("/recharge").wrap(actix_web::middleware::from_fn(signature_middleware)).route(web::post().to(recharge::recharge_handler)))
                     .service(web::resource("/recharge/logs").route(web::get().to(recharge::get_recharge_logs_handler)))
                     
                     // 软件相关路由
                     .service(web::resource("/software").route(web::get().to(software::get_all_software_handler)))
                     .service(web::resource("/software/{software_id}/access").route(web::get().to(software::check_software_access_handler)))
+                    .service(web::resource("/software/{software_id}/license").route(web::post().to(software::issue_license_handler)))
+                    
+                    // WebSocket推送连接
+                    .service(web::resource("/ws").route(web::get().to(push::push_connection_handler)))
+                    
+                    // 离线激活路由
+                    .service(web::resource("/activation/offline").route(web::post().to(activation::offline_activation_handler)))
             )
     );
 }
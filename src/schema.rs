@@ -6,13 +6,15 @@ use diesel::sql_types::*;
 table! {
     login_logs (id) {
         id -> Int4,
-        user_id -> Int4,
+        user_id -> Nullable<Int4>,
         login_time -> Timestamptz,
         hardware_code -> Varchar,
         software_version -> Varchar,
         ip_address -> Varchar,
         status -> Varchar,
         created_at -> Timestamptz,
+        username -> Varchar,
+        failure_reason -> Nullable<Varchar>,
     }
 }
 
@@ -20,7 +22,7 @@ table! {
     online_users (id) {
         id -> Int4,
         user_id -> Int4,
-        session_token -> Varchar,
+        session_token -> Text,
         login_time -> Timestamptz,
         hardware_code -> Varchar,
         software_version -> Varchar,
@@ -102,6 +104,8 @@ table! {
         expires_at -> Timestamptz,
         used -> Bool,
         created_at -> Timestamptz,
+        failed_attempts -> Int4,
+        purpose -> Varchar,
     }
 }
 
@@ -115,5 +119,206 @@ table! {
     }
 }
 
+table! {
+    refresh_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        session_id -> Int4,
+        token_hash -> Varchar,
+        expires_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    vip_level_policies (vip_level) {
+        vip_level -> Int4,
+        max_sessions -> Int4,
+        session_limit_action -> Varchar,
+        allow_shared_hardware -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        max_devices -> Int4,
+        unbind_cooldown_hours -> Int4,
+        unbind_penalty_hours -> Int4,
+    }
+}
+
+table! {
+    login_lockouts (id) {
+        id -> Int4,
+        scope -> Varchar,
+        scope_key -> Varchar,
+        failure_count -> Int4,
+        window_started_at -> Timestamptz,
+        lockout_count -> Int4,
+        locked_until -> Nullable<Timestamptz>,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    user_totp (id) {
+        id -> Int4,
+        user_id -> Int4,
+        secret -> Varchar,
+        enabled -> Bool,
+        last_used_step -> Nullable<Int8>,
+        confirmed_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    totp_backup_codes (id) {
+        id -> Int4,
+        user_id -> Int4,
+        code_hash -> Varchar,
+        used_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    offline_activations (id) {
+        id -> Int4,
+        user_id -> Nullable<Int4>,
+        software_id -> Int4,
+        hardware_code -> Varchar,
+        request_nonce -> Varchar,
+        source -> Varchar,
+        card_code -> Nullable<Varchar>,
+        vip_level -> Int4,
+        expires_at -> Timestamptz,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    email_change_requests (id) {
+        id -> Int4,
+        user_id -> Int4,
+        old_email -> Varchar,
+        new_email -> Varchar,
+        code -> Varchar,
+        cancel_token_hash -> Varchar,
+        expires_at -> Timestamptz,
+        confirmed_at -> Nullable<Timestamptz>,
+        cancelled_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        failed_attempts -> Int4,
+    }
+}
+
+table! {
+    email_dispatch_logs (id) {
+        id -> Int4,
+        user_id -> Int4,
+        purpose -> Varchar,
+        email -> Varchar,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    magic_link_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        token_hash -> Varchar,
+        hardware_code -> Varchar,
+        ip_address -> Varchar,
+        expires_at -> Timestamptz,
+        used_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    user_devices (id) {
+        id -> Int4,
+        user_id -> Int4,
+        hardware_code -> Varchar,
+        bound_at -> Timestamptz,
+        last_login_at -> Timestamptz,
+        unbound_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    device_fingerprint_components (id) {
+        id -> Int4,
+        device_id -> Int4,
+        component -> Varchar,
+        value -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    device_fingerprint_changes (id) {
+        id -> Int4,
+        device_id -> Int4,
+        component -> Varchar,
+        old_value -> Nullable<Varchar>,
+        new_value -> Varchar,
+        source -> Varchar,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    software_client_secrets (software_id) {
+        software_id -> Int4,
+        client_secret -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    session_commands (id) {
+        id -> Int4,
+        session_id -> Int4,
+        user_id -> Int4,
+        command_type -> Varchar,
+        message -> Nullable<Text>,
+        payload -> Nullable<Text>,
+        expires_at -> Nullable<Timestamptz>,
+        delivered_at -> Nullable<Timestamptz>,
+        acknowledged_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    session_history (id) {
+        id -> Int4,
+        session_id -> Int4,
+        user_id -> Int4,
+        hardware_code -> Varchar,
+        software_version -> Varchar,
+        ip_address -> Varchar,
+        started_at -> Timestamptz,
+        last_activity_at -> Timestamptz,
+        ended_at -> Timestamptz,
+        end_reason -> Varchar,
+        duration_seconds -> Int8,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    consumed_two_factor_tokens (jti) {
+        jti -> Varchar,
+        user_id -> Int4,
+        expires_at -> Timestamptz,
+        created_at -> Timestamptz,
+    }
+}
+
 // 导出表，以便在其他文件中使用
-allow_tables_to_appear_in_same_query!(login_logs, online_users, recharge_cards, recharge_logs, software, users, verification_codes, blacklist,);
+allow_tables_to_appear_in_same_query!(login_logs, online_users, recharge_cards, recharge_logs, software, users, verification_codes, blacklist, refresh_tokens, vip_level_policies, login_lockouts, user_totp, totp_backup_codes, offline_activations, email_change_requests, email_dispatch_logs, magic_link_tokens, user_devices, device_fingerprint_components, device_fingerprint_changes, software_client_secrets, session_commands, session_history, consumed_two_factor_tokens,);
@@ -0,0 +1,174 @@
+use diesel::prelude::*;
+use chrono::{Utc, Duration};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use validator::Validate;
+use crate::database::{models::*, Pool};
+use crate::schema::*;
+use crate::config::Config;
+use crate::errors::AppError;
+use crate::utils::jwt::sign_claims;
+
+type Result<T> = std::result::Result<T, AppError>;
+
+/// 激活来源：消耗充值卡密
+pub const ACTIVATION_SOURCE_CARD: &str = "card";
+/// 激活来源：消耗用户VIP时长
+pub const ACTIVATION_SOURCE_VIP_TIME: &str = "vip_time";
+
+/// 处理离线激活请求码，消耗卡密或VIP时长后返回签名的激活码
+///
+/// `user_id` 为提交请求的用户（管理员代为激活时为指定的用户，可为空）。
+/// 同一请求码重复提交时不会重复消耗，直接重新签发已有记录的激活码。
+pub async fn activate_offline(
+    pool: &Pool,
+    request_code: &str,
+    user_id: Option<i32>,
+    card_code: Option<&str>,
+    days: Option<i32>,
+    config: &Config,
+) -> Result<(String, OfflineActivation)> {
+    // 激活码必须使用非对称密钥签名，否则离线客户端无法验证
+    if config.jwt_keys.signing_key.is_none() {
+        return Err(AppError::InternalServerError("Offline activation signing key is not configured".to_string()));
+    }
+
+    let request = decode_request_code(request_code)?;
+
+    let mut conn = pool.get()?;
+
+    let software = software::table
+        .find(request.software_id)
+        .first::<Software>(&mut conn)?;
+
+    // 同一请求码已激活过时直接重新签发
+    let existing = offline_activations::table
+        .filter(offline_activations::request_nonce.eq(&request.nonce))
+        .first::<OfflineActivation>(&mut conn)
+        .optional()?;
+
+    if let Some(activation) = existing {
+        if activation.hardware_code != request.hardware_code || activation.software_id != request.software_id {
+            return Err(AppError::BadRequest("Request code nonce has already been used".to_string()));
+        }
+        if user_id.is_some() && activation.user_id.is_some() && activation.user_id != user_id {
+            return Err(AppError::Forbidden("Request code was activated by another account".to_string()));
+        }
+        let activation_code = sign_activation(&activation, config)?;
+        return Ok((activation_code, activation));
+    }
+
+    let card_code = card_code.map(str::trim).filter(|code| !code.is_empty());
+
+    let activation = conn.transaction(|conn| {
+        let (source, vip_level, expires_at) = match card_code {
+            Some(card_code) => consume_card(conn, card_code, user_id)?,
+            None => {
+                let user_id = user_id.ok_or_else(|| AppError::BadRequest("Either card_code or user_id must be provided".to_string()))?;
+                let days = days.ok_or_else(|| AppError::BadRequest("Days must be provided when no card code is used".to_string()))?;
+                consume_vip_time(conn, user_id, days)?
+            }
+        };
+
+        if vip_level < software.required_vip_level {
+            return Err(AppError::Forbidden("VIP level is too low for this software".to_string()));
+        }
+
+        let activation = diesel::insert_into(offline_activations::table)
+            .values((
+                offline_activations::user_id.eq(user_id),
+                offline_activations::software_id.eq(software.id),
+                offline_activations::hardware_code.eq(&request.hardware_code),
+                offline_activations::request_nonce.eq(&request.nonce),
+                offline_activations::source.eq(source),
+                offline_activations::card_code.eq(card_code),
+                offline_activations::vip_level.eq(vip_level),
+                offline_activations::expires_at.eq(expires_at),
+                offline_activations::created_at.eq(Utc::now()),
+            ))
+            .get_result::<OfflineActivation>(conn)?;
+
+        Ok::<_, AppError>(activation)
+    })?;
+
+    let activation_code = sign_activation(&activation, config)?;
+
+    Ok((activation_code, activation))
+}
+
+/// 解析客户端生成的请求码（Base64URL编码的JSON）
+fn decode_request_code(request_code: &str) -> Result<ActivationRequestCode> {
+    let bytes = URL_SAFE_NO_PAD.decode(request_code.trim().trim_end_matches('='))
+        .map_err(|_| AppError::BadRequest("Invalid request code".to_string()))?;
+    let request = serde_json::from_slice::<ActivationRequestCode>(&bytes)
+        .map_err(|_| AppError::BadRequest("Invalid request code".to_string()))?;
+
+    request.validate()
+        .map_err(|err| AppError::BadRequest(err.to_string()))?;
+
+    Ok(request)
+}
+
+/// 消耗一张未使用的充值卡密，返回激活来源、VIP等级和到期时间
+fn consume_card(conn: &mut PgConnection, card_code: &str, user_id: Option<i32>) -> Result<(&'static str, i32, chrono::DateTime<Utc>)> {
+    let card = recharge_cards::table
+        .filter(recharge_cards::card_code.eq(card_code))
+        .for_update()
+        .first::<RechargeCard>(conn)
+        .optional()?
+        .ok_or_else(|| AppError::BadRequest("Card not found".to_string()))?;
+
+    if card.is_used {
+        return Err(AppError::BadRequest("Card already used".to_string()));
+    }
+
+    diesel::update(recharge_cards::table.find(card.id))
+        .set((
+            recharge_cards::is_used.eq(true),
+            recharge_cards::used_at.eq(Utc::now()),
+            recharge_cards::used_by.eq(user_id),
+        ))
+        .execute(conn)?;
+
+    Ok((ACTIVATION_SOURCE_CARD, card.vip_level, Utc::now() + Duration::days(card.duration_days as i64)))
+}
+
+/// 从用户剩余的VIP时长中扣除指定天数，返回激活来源、VIP等级和到期时间
+fn consume_vip_time(conn: &mut PgConnection, user_id: i32, days: i32) -> Result<(&'static str, i32, chrono::DateTime<Utc>)> {
+    let user = users::table
+        .find(user_id)
+        .for_update()
+        .first::<User>(conn)?;
+
+    let vip_level = user.current_vip_level();
+    let duration = Duration::days(days as i64);
+    let vip_expires_at = match user.vip_expires_at {
+        Some(expires_at) if vip_level > 0 && expires_at - duration > Utc::now() => expires_at,
+        _ => return Err(AppError::BadRequest("Insufficient remaining VIP time".to_string())),
+    };
+
+    diesel::update(users::table.find(user_id))
+        .set((
+            users::vip_expires_at.eq(vip_expires_at - duration),
+            users::updated_at.eq(Utc::now()),
+        ))
+        .execute(conn)?;
+
+    Ok((ACTIVATION_SOURCE_VIP_TIME, vip_level, Utc::now() + duration))
+}
+
+/// 为激活记录签发激活码
+fn sign_activation(activation: &OfflineActivation, config: &Config) -> Result<String> {
+    let claims = OfflineActivationClaims {
+        sub: activation.user_id.map(|id| id.to_string()).unwrap_or_default(),
+        token_type: "offline_activation".to_string(),
+        hardware_code: activation.hardware_code.clone(),
+        software_id: activation.software_id,
+        vip_level: activation.vip_level,
+        nonce: activation.request_nonce.clone(),
+        iat: activation.created_at.timestamp(),
+        exp: activation.expires_at.timestamp(),
+    };
+
+    sign_claims(&claims, config)
+}
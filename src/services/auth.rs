@@ -1,27 +1,114 @@
 use diesel::prelude::*;
 use chrono::{Utc, DateTime, Duration};
+use log::{info, warn};
 use crate::database::{models::*, Pool};
+use crate::services::device::bind_device;
+use crate::services::heartbeat_buffer::HEARTBEAT_BUFFER;
+use crate::services::session_history::{SESSION_END_BANNED, SESSION_END_KICKED, SESSION_END_LOGOUT, end_sessions};
+use crate::services::lockout::{check_login_lockout, record_login_failure, reset_login_failures};
+use crate::services::two_factor::{consume_two_factor_token, is_two_factor_enabled, verify_second_factor};
+use crate::services::email::{
+    EMAIL_PURPOSE_MAGIC_LINK, EMAIL_PURPOSE_PASSWORD_RESET, email_cooldown_remaining, find_active_verification_code,
+    record_email_dispatch, record_verification_failure, store_verification_code, verification_code_matches,
+};
 use crate::utils::{crypto::*, jwt::*};
 use crate::schema::*;
-use crate::config::Config;
+use crate::config::{Config, VERIFIED_EMAIL_ACTION_LOGIN, VERIFIED_EMAIL_ACTION_PASSWORD_RESET};
 use crate::errors::AppError;
 
 type Result<T> = std::result::Result<T, AppError>;
 
-pub async fn register_user(pool: &Pool, req: RegisterRequest, config: &Config) -> Result<(User, String)> {
-    let mut conn = pool.get()?;
+/// 登录或刷新后下发给客户端的令牌对
+pub struct SessionTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// 会话的心跳上报间隔（秒）
+    pub status_interval: i32,
+}
+
+/// 登录结果：直接创建会话，或需要先完成两步验证
+pub enum LoginOutcome {
+    /// 登录成功，已创建会话
+    Authenticated(Box<User>, SessionTokens),
+    /// 账号已启用两步验证，返回短期有效的待完成令牌
+    TwoFactorRequired(String),
+    /// 配置要求登录前验证邮箱，已重新发送验证码，返回新的激活令牌
+    EmailVerificationRequired(String),
+}
+
+/// 为指定会话签发刷新令牌，并将其哈希持久化
+fn issue_refresh_token(conn: &mut PgConnection, user: &User, session_id: i32, config: &Config) -> Result<String> {
+    let refresh_token = generate_refresh_token(user.id, &user.username, config)?;
     
-    // 检查黑名单
-    let is_blacklisted = blacklist::table
+    diesel::insert_into(refresh_tokens::table)
+        .values((
+            refresh_tokens::user_id.eq(user.id),
+            refresh_tokens::session_id.eq(session_id),
+            refresh_tokens::token_hash.eq(sha256_hex(&refresh_token)),
+            refresh_tokens::expires_at.eq(Utc::now() + Duration::days(REFRESH_TOKEN_TTL_DAYS)),
+            refresh_tokens::created_at.eq(Utc::now()),
+        ))
+        .execute(conn)?;
+    
+    Ok(refresh_token)
+}
+
+/// 查找与用户名、硬件码或IP匹配的黑名单记录
+fn find_blacklist_entry(conn: &mut PgConnection, username: &str, hardware_code: &str, ip: &str) -> Result<Option<Blacklist>> {
+    let entry = blacklist::table
         .filter(
-            blacklist::username.eq(&req.username)
-            .or(blacklist::hardware_code.eq(&req.hardware_code))
-            .or(blacklist::ip_address.eq(&req.ip_address))
+            blacklist::username.eq(username)
+            .or(blacklist::hardware_code.eq(hardware_code))
+            .or(blacklist::ip_address.eq(ip))
         )
-        .first::<crate::database::models::Blacklist>(&mut conn)
+        .first::<Blacklist>(conn)
         .optional()?;
     
-    if is_blacklisted.is_some() {
+    Ok(entry)
+}
+
+/// 检查邮箱是否未被其他账号使用，注册和修改邮箱时共用
+pub fn ensure_email_available(conn: &mut PgConnection, email: &str) -> Result<()> {
+    let existing_email = users::table
+        .filter(users::email.eq(email))
+        .first::<User>(conn)
+        .optional()?;
+    
+    if existing_email.is_some() {
+        return Err(AppError::BadRequest("Email already exists".to_string()));
+    }
+    
+    Ok(())
+}
+
+/// 按配置检查用户执行指定操作前是否已验证邮箱
+pub fn ensure_email_verified(user: &User, action: &str, config: &Config) -> Result<()> {
+    if !user.email_verified && config.requires_verified_email(action) {
+        return Err(AppError::EmailNotVerified("Email address has not been verified".to_string()));
+    }
+    
+    Ok(())
+}
+
+/// 按用户ID检查邮箱是否已验证，操作不要求验证时不查询数据库
+pub async fn check_email_verified(pool: &Pool, user_id: i32, action: &str, config: &Config) -> Result<()> {
+    if !config.requires_verified_email(action) {
+        return Ok(());
+    }
+    
+    let mut conn = pool.get()?;
+    let user = users::table
+        .find(user_id)
+        .first::<User>(&mut conn)?;
+    
+    ensure_email_verified(&user, action, config)
+}
+
+pub async fn register_user(pool: &Pool, req: RegisterRequest, config: &Config) -> Result<(User, String)> {
+    let mut conn = pool.get()?;
+    
+    // 检查黑名单
+    if find_blacklist_entry(&mut conn, &req.username, &req.hardware_code, &req.ip_address)?.is_some() {
         return Err(AppError::BadRequest("Device exception, cannot communicate".to_string()));
     }
     
@@ -36,20 +123,13 @@ pub async fn register_user(pool: &Pool, req: RegisterRequest, config: &Config) -
     }
     
     // 检查邮箱是否已存在
-    let existing_email = users::table
-        .filter(users::email.eq(&req.email))
-        .first::<User>(&mut conn)
-        .optional()?;
-    
-    if existing_email.is_some() {
-        return Err(AppError::BadRequest("Email already exists".to_string()));
-    }
+    ensure_email_available(&mut conn, &req.email)?;
     
     // 验证邮箱格式
     crate::utils::email::validate_email(&req.email)?;
     
     // 加密密码
-    let password_hash = hash_password(&req.password, config)?;
+    let password_hash = hash_password(&req.password, &[&req.username, &req.email], config)?;
     
     // 创建用户
     let new_user = diesel::insert_into(users::table)
@@ -74,83 +154,453 @@ pub async fn register_user(pool: &Pool, req: RegisterRequest, config: &Config) -
     Ok((new_user, activation_token))
 }
 
-pub async fn login_user(pool: &Pool, req: LoginRequest, ip: &str, config: &Config) -> Result<(User, String)> {
+/// 登录失败原因：密码错误
+pub const LOGIN_FAILURE_BAD_PASSWORD: &str = "bad_password";
+/// 登录失败原因：命中黑名单
+pub const LOGIN_FAILURE_BLACKLISTED: &str = "blacklisted";
+/// 登录失败原因：用户不存在
+pub const LOGIN_FAILURE_UNKNOWN_USER: &str = "unknown_user";
+/// 登录失败原因：用户名或IP已被锁定
+pub const LOGIN_FAILURE_LOCKED: &str = "locked";
+/// 登录失败原因：两步验证码错误
+pub const LOGIN_FAILURE_BAD_TOTP: &str = "bad_totp";
+/// 登录失败原因：超出会话数量上限
+pub const LOGIN_FAILURE_SESSION_LIMIT: &str = "session_limit";
+/// 登录失败原因：邮箱未验证
+pub const LOGIN_FAILURE_EMAIL_NOT_VERIFIED: &str = "email_not_verified";
+/// 登录失败原因：未绑定的新设备超出绑定上限
+pub const LOGIN_FAILURE_DEVICE_LIMIT: &str = "device_limit";
+
+/// 客户端登录时上报的设备信息
+pub struct LoginDevice<'a> {
+    pub hardware_code: &'a str,
+    pub software_version: &'a str,
+    pub ip: &'a str,
+    pub fingerprint: Option<&'a HardwareFingerprint>,
+}
+
+/// 写入登录日志，failure_reason 为空表示登录成功
+fn log_login_attempt(
+    conn: &mut PgConnection,
+    user_id: Option<i32>,
+    username: &str,
+    device: &LoginDevice,
+    failure_reason: Option<&str>,
+) -> Result<()> {
+    diesel::insert_into(login_logs::table)
+        .values((
+            login_logs::user_id.eq(user_id),
+            login_logs::username.eq(username),
+            login_logs::login_time.eq(Utc::now()),
+            login_logs::hardware_code.eq(device.hardware_code),
+            login_logs::software_version.eq(device.software_version),
+            login_logs::ip_address.eq(device.ip),
+            login_logs::status.eq(if failure_reason.is_some() { "failed" } else { "success" }),
+            login_logs::failure_reason.eq(failure_reason),
+            login_logs::created_at.eq(Utc::now()),
+        ))
+        .execute(conn)?;
+    
+    Ok(())
+}
+
+/// 用当前的Argon2id参数重新计算密码哈希，失败时只记录警告，不影响本次登录
+fn upgrade_password_hash(conn: &mut PgConnection, user_id: i32, password: &str, config: &Config) {
+    let result = compute_password_hash(password, config).and_then(|password_hash| {
+        diesel::update(users::table.find(user_id))
+            .set(users::password_hash.eq(password_hash))
+            .execute(conn)?;
+        Ok(())
+    });
+
+    if let Err(err) = result {
+        warn!("Failed to upgrade password hash for user {}: {}", user_id, err);
+    }
+}
+
+pub async fn login_user(pool: &Pool, req: LoginRequest, ip: &str, config: &Config) -> Result<LoginOutcome> {
     let mut conn = pool.get()?;
+    let device = LoginDevice {
+        hardware_code: &req.hardware_code,
+        software_version: &req.software_version,
+        ip,
+        fingerprint: req.fingerprint.as_ref(),
+    };
     
-    // 检查黑名单
-    let is_blacklisted = blacklist::table
-        .filter(
-            blacklist::username.eq(&req.username)
-            .or(blacklist::hardware_code.eq(&req.hardware_code))
-            .or(blacklist::ip_address.eq(ip))
-        )
-        .first::<crate::database::models::Blacklist>(&mut conn)
+    // 查找用户
+    let user = users::table
+        .filter(users::username.eq(&req.username))
+        .first::<User>(&mut conn)
         .optional()?;
+    let user_id = user.as_ref().map(|user| user.id);
+    
+    // 检查用户名或IP是否已被锁定
+    if let Err(err) = check_login_lockout(&mut conn, &req.username, ip) {
+        log_login_attempt(&mut conn, user_id, &req.username, &device, Some(LOGIN_FAILURE_LOCKED))?;
+        return Err(err);
+    }
     
-    if is_blacklisted.is_some() {
+    // 检查黑名单
+    if find_blacklist_entry(&mut conn, &req.username, &req.hardware_code, ip)?.is_some() {
+        log_login_attempt(&mut conn, user_id, &req.username, &device, Some(LOGIN_FAILURE_BLACKLISTED))?;
         return Err(AppError::BadRequest("Device exception, cannot communicate".to_string()));
     }
     
-    // 查找用户
-    let user = users::table
-        .filter(users::username.eq(&req.username))
-        .first::<User>(&mut conn)?;
+    let user = match user {
+        Some(user) => user,
+        None => {
+            record_login_failure(&mut conn, &req.username, ip, config)?;
+            log_login_attempt(&mut conn, None, &req.username, &device, Some(LOGIN_FAILURE_UNKNOWN_USER))?;
+            return Err(AppError::NotFound("User not found".to_string()));
+        }
+    };
     
     // 验证密码
     let is_password_valid = verify_password(&req.password, &user.password_hash)?;
     if !is_password_valid {
+        record_login_failure(&mut conn, &req.username, ip, config)?;
+        log_login_attempt(&mut conn, Some(user.id), &req.username, &device, Some(LOGIN_FAILURE_BAD_PASSWORD))?;
         return Err(AppError::Unauthorized("Invalid password".to_string()));
     }
     
-    // 无论邮箱是否已验证，都正常生成访问令牌
-    let access_token = generate_access_token(user.id, &user.username, config)?;
-    let refresh_token = generate_refresh_token(user.id, &user.username, config)?;
+    reset_login_failures(&mut conn, &user.username)?;
     
-    // 记录登录日志
-    diesel::insert_into(login_logs::table)
-        .values((
-            login_logs::user_id.eq(user.id),
-            login_logs::login_time.eq(Utc::now()),
-            login_logs::hardware_code.eq(&req.hardware_code),
-            login_logs::software_version.eq(&req.software_version),
-            login_logs::ip_address.eq(ip),
-            login_logs::status.eq("success"),
-            login_logs::created_at.eq(Utc::now()),
-        ))
+    // 旧的bcrypt哈希或参数已过时的哈希，在登录成功时用当前参数重新计算
+    if password_needs_rehash(&user.password_hash, config) {
+        upgrade_password_hash(&mut conn, user.id, &req.password, config);
+    }
+    
+    // 配置要求登录前验证邮箱时，重新发送验证码并返回激活令牌
+    if ensure_email_verified(&user, VERIFIED_EMAIL_ACTION_LOGIN, config).is_err() {
+        log_login_attempt(&mut conn, Some(user.id), &req.username, &device, Some(LOGIN_FAILURE_EMAIL_NOT_VERIFIED))?;
+        // 仍在重新发送冷却期内或邮件发送失败时，只签发新的激活令牌，客户端可稍后重新发送验证码
+        let activation_token = match crate::services::email::send_verification_email(pool, &user, config).await {
+            Ok(activation_token) => activation_token,
+            Err(AppError::TooManyRequests(_)) => generate_activation_token(user.id, &user.email, config)?,
+            Err(err) => {
+                warn!("Failed to send verification email to user {} during login: {}", user.id, err);
+                generate_activation_token(user.id, &user.email, config)?
+            }
+        };
+        return Ok(LoginOutcome::EmailVerificationRequired(activation_token));
+    }
+    
+    // 已启用两步验证的账号需要先换取待完成令牌
+    if is_two_factor_enabled(&mut conn, user.id)? {
+        let pending_token = generate_two_factor_token(user.id, &user.username, config)?;
+        return Ok(LoginOutcome::TwoFactorRequired(pending_token));
+    }
+    
+    // 邮箱验证策略已在上面检查，此处直接创建会话
+    let (user, tokens) = create_session(&mut conn, &user, &device, config)?;
+    Ok(LoginOutcome::Authenticated(Box::new(user), tokens))
+}
+
+/// 使用两步验证待完成令牌和验证码完成登录
+pub async fn complete_two_factor_login(pool: &Pool, req: VerifyTwoFactorLoginRequest, ip: &str, config: &Config) -> Result<(User, SessionTokens)> {
+    let mut conn = pool.get()?;
+    let device = LoginDevice {
+        hardware_code: &req.hardware_code,
+        software_version: &req.software_version,
+        ip,
+        fingerprint: req.fingerprint.as_ref(),
+    };
+    
+    // 验证待完成令牌
+    let claims = verify_two_factor_token(&req.pending_token, config)?;
+    let user_id = claims.sub.parse::<i32>()?;
+    
+    let user = users::table
+        .find(user_id)
+        .first::<User>(&mut conn)?;
+    
+    // 检查用户名或IP是否已被锁定
+    if let Err(err) = check_login_lockout(&mut conn, &user.username, ip) {
+        log_login_attempt(&mut conn, Some(user.id), &user.username, &device, Some(LOGIN_FAILURE_LOCKED))?;
+        return Err(err);
+    }
+    
+    // 检查黑名单
+    if find_blacklist_entry(&mut conn, &user.username, &req.hardware_code, ip)?.is_some() {
+        log_login_attempt(&mut conn, Some(user.id), &user.username, &device, Some(LOGIN_FAILURE_BLACKLISTED))?;
+        return Err(AppError::BadRequest("Device exception, cannot communicate".to_string()));
+    }
+    
+    // 校验TOTP验证码或备用码，错误次数计入登录锁定
+    if !verify_second_factor(&mut conn, user.id, &req.code)? {
+        record_login_failure(&mut conn, &user.username, ip, config)?;
+        log_login_attempt(&mut conn, Some(user.id), &user.username, &device, Some(LOGIN_FAILURE_BAD_TOTP))?;
+        return Err(AppError::Unauthorized("Invalid two-factor code".to_string()));
+    }
+    
+    reset_login_failures(&mut conn, &user.username)?;
+    
+    // 验证码正确后作废待完成令牌，防止在有效期内重放
+    consume_two_factor_token(&mut conn, &claims)?;
+    
+    create_session(&mut conn, &user, &device, config)
+}
+
+/// 申请免密登录链接
+///
+/// 为避免泄露邮箱是否已注册，邮箱不存在、命中黑名单或仍在发送冷却期内时同样返回成功，但不发送邮件；
+/// 黑名单在兑换链接时同样会检查。同一用户之前未使用的登录链接会被作废，新链接只能在发起请求的硬件上使用。
+pub async fn request_magic_link(pool: &Pool, req: MagicLinkRequest, ip: &str, config: &Config) -> Result<()> {
+    if !config.magic_link_enabled {
+        return Err(AppError::Forbidden("Magic link login is disabled".to_string()));
+    }
+    
+    let mut conn = pool.get()?;
+    
+    // 查找用户
+    let user = match users::table
+        .filter(users::email.eq(&req.email))
+        .first::<User>(&mut conn)
+        .optional()? {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+    
+    // 命中黑名单时不发送邮件，但不通过响应暴露账号是否存在
+    if find_blacklist_entry(&mut conn, &user.username, &req.hardware_code, ip)?.is_some() {
+        info!("Magic login link for user {} skipped due to blacklist", user.id);
+        return Ok(());
+    }
+    
+    // 仍在冷却期或已达到每日发送上限时不再发送
+    match email_cooldown_remaining(&mut conn, user.id, EMAIL_PURPOSE_MAGIC_LINK, config) {
+        Ok(0) => {}
+        Ok(_) | Err(AppError::TooManyRequests(_)) => {
+            info!("Magic login link for user {} skipped due to rate limit", user.id);
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+    }
+    
+    let token = generate_random_token(32);
+    let ttl = Duration::seconds(config.magic_link_ttl.as_secs() as i64);
+    
+    conn.transaction(|conn| {
+        // 作废之前未使用的登录链接
+        diesel::delete(magic_link_tokens::table)
+            .filter(magic_link_tokens::user_id.eq(user.id))
+            .filter(magic_link_tokens::used_at.is_null())
+            .execute(conn)?;
+        
+        diesel::insert_into(magic_link_tokens::table)
+            .values((
+                magic_link_tokens::user_id.eq(user.id),
+                magic_link_tokens::token_hash.eq(sha256_hex(&token)),
+                magic_link_tokens::hardware_code.eq(&req.hardware_code),
+                magic_link_tokens::ip_address.eq(ip),
+                magic_link_tokens::expires_at.eq(Utc::now() + ttl),
+                magic_link_tokens::created_at.eq(Utc::now()),
+            ))
+            .execute(conn)?;
+        
+        record_email_dispatch(conn, user.id, EMAIL_PURPOSE_MAGIC_LINK, &user.email)
+    })?;
+    
+    // 发送登录链接邮件
+    crate::services::email::send_magic_link_email(&user, &token, config).await
+}
+
+/// 使用免密登录链接中的令牌登录
+///
+/// 令牌只能使用一次，且必须在发起请求的硬件上兑换。登录前同样执行锁定和黑名单检查，
+/// 账号启用了两步验证时仍需完成两步验证。兑换成功即证明邮箱可用，会同时将邮箱标记为已验证。
+pub async fn redeem_magic_link(pool: &Pool, req: RedeemMagicLinkRequest, ip: &str, config: &Config) -> Result<LoginOutcome> {
+    if !config.magic_link_enabled {
+        return Err(AppError::Forbidden("Magic link login is disabled".to_string()));
+    }
+    
+    let mut conn = pool.get()?;
+    let device = LoginDevice {
+        hardware_code: &req.hardware_code,
+        software_version: &req.software_version,
+        ip,
+        fingerprint: req.fingerprint.as_ref(),
+    };
+    
+    // 查找未使用且未过期的登录令牌
+    let magic_link = magic_link_tokens::table
+        .filter(magic_link_tokens::token_hash.eq(sha256_hex(&req.token)))
+        .filter(magic_link_tokens::used_at.is_null())
+        .filter(magic_link_tokens::expires_at.gt(Utc::now()))
+        .first::<MagicLinkToken>(&mut conn)
+        .optional()?
+        .ok_or_else(|| AppError::Unauthorized("Login link is invalid or has expired".to_string()))?;
+    
+    // 登录链接只能在发起请求的设备上使用
+    if magic_link.hardware_code != req.hardware_code {
+        return Err(AppError::Unauthorized("Login link was requested from a different device".to_string()));
+    }
+    
+    let user = users::table
+        .find(magic_link.user_id)
+        .first::<User>(&mut conn)?;
+    
+    // 检查用户名或IP是否已被锁定
+    if let Err(err) = check_login_lockout(&mut conn, &user.username, ip) {
+        log_login_attempt(&mut conn, Some(user.id), &user.username, &device, Some(LOGIN_FAILURE_LOCKED))?;
+        return Err(err);
+    }
+    
+    // 检查黑名单
+    if find_blacklist_entry(&mut conn, &user.username, &req.hardware_code, ip)?.is_some() {
+        log_login_attempt(&mut conn, Some(user.id), &user.username, &device, Some(LOGIN_FAILURE_BLACKLISTED))?;
+        return Err(AppError::BadRequest("Device exception, cannot communicate".to_string()));
+    }
+    
+    // 标记令牌已使用，并发兑换时只有一个请求能成功
+    let consumed = diesel::update(magic_link_tokens::table.find(magic_link.id))
+        .filter(magic_link_tokens::used_at.is_null())
+        .set(magic_link_tokens::used_at.eq(Utc::now()))
         .execute(&mut conn)?;
+    if consumed == 0 {
+        return Err(AppError::Unauthorized("Login link is invalid or has expired".to_string()));
+    }
     
-    // 踢掉旧的在线会话
-    diesel::delete(online_users::table)
+    // 能收到登录链接即说明邮箱可用
+    let user = if user.email_verified {
+        user
+    } else {
+        diesel::update(users::table.find(user.id))
+            .set((
+                users::email_verified.eq(true),
+                users::updated_at.eq(Utc::now()),
+            ))
+            .get_result::<User>(&mut conn)?
+    };
+    
+    // 已启用两步验证的账号需要先换取待完成令牌
+    if is_two_factor_enabled(&mut conn, user.id)? {
+        let pending_token = generate_two_factor_token(user.id, &user.username, config)?;
+        return Ok(LoginOutcome::TwoFactorRequired(pending_token));
+    }
+    
+    let (user, tokens) = create_session(&mut conn, &user, &device, config)?;
+    Ok(LoginOutcome::Authenticated(Box::new(user), tokens))
+}
+
+/// 获取VIP等级对应的会话策略
+///
+/// 取不高于该等级的最高一级策略，未配置任何策略时使用默认策略
+pub fn get_vip_level_policy(conn: &mut PgConnection, vip_level: i32) -> Result<VipLevelPolicy> {
+    let policy = vip_level_policies::table
+        .filter(vip_level_policies::vip_level.le(vip_level))
+        .order_by(vip_level_policies::vip_level.desc())
+        .first::<VipLevelPolicy>(conn)
+        .optional()?;
+    
+    Ok(policy.unwrap_or_else(|| VipLevelPolicy::default_for(vip_level)))
+}
+
+/// 按VIP等级策略为已通过认证的用户创建在线会话，并签发访问令牌和刷新令牌
+///
+/// 在事务中完成，任一步骤失败时设备绑定、踢下线和新会话一并回滚。
+fn create_session(
+    conn: &mut PgConnection,
+    user: &User,
+    device: &LoginDevice,
+    config: &Config,
+) -> Result<(User, SessionTokens)> {
+    let mut failure_reason = None;
+    let result = conn.transaction(|conn| create_session_locked(conn, user, device, config, &mut failure_reason));
+    
+    // 因会话或设备数量上限被拒绝时，登录日志在事务回滚后单独写入
+    if let Some(failure_reason) = failure_reason {
+        log_login_attempt(conn, Some(user.id), &user.username, device, Some(failure_reason))?;
+    }
+    
+    result
+}
+
+/// 锁定用户记录后创建会话，避免并发登录同时通过会话数量和设备绑定上限检查
+fn create_session_locked(
+    conn: &mut PgConnection,
+    user: &User,
+    device: &LoginDevice,
+    config: &Config,
+    failure_reason: &mut Option<&'static str>,
+) -> Result<(User, SessionTokens)> {
+    let user = &users::table
+        .find(user.id)
+        .for_update()
+        .first::<User>(conn)?;
+    
+    let policy = get_vip_level_policy(conn, user.current_vip_level())?;
+    
+    // 加载该用户现有的在线会话，按登录时间从早到晚排列
+    let existing_sessions = online_users::table
         .filter(online_users::user_id.eq(user.id))
-        .execute(&mut conn)?;
+        .order_by(online_users::login_time.asc())
+        .load::<OnlineUser>(conn)?;
+    
+    // 不允许共享硬件码时，同一硬件上的旧会话将被新会话替换
+    let (replaced, remaining): (Vec<OnlineUser>, Vec<OnlineUser>) = existing_sessions
+        .into_iter()
+        .partition(|session| !policy.allow_shared_hardware && session.hardware_code == device.hardware_code);
+    
+    // 超出会话数量上限时，按策略拒绝新登录或踢掉最早的会话
+    let max_sessions = policy.max_sessions.max(1) as usize;
+    let overflow = (remaining.len() + 1).saturating_sub(max_sessions);
+    if overflow > 0 && policy.session_limit_action == "reject" {
+        *failure_reason = Some(LOGIN_FAILURE_SESSION_LIMIT);
+        return Err(AppError::Forbidden(format!("Maximum number of concurrent sessions ({}) reached", max_sessions)));
+    }
+    
+    // 绑定登录设备，未绑定的新设备超出绑定上限时拒绝登录
+    if let Err(err) = bind_device(conn, user.id, device.hardware_code, device.fingerprint, &policy, config) {
+        if let AppError::Forbidden(_) = err {
+            *failure_reason = Some(LOGIN_FAILURE_DEVICE_LIMIT);
+        }
+        return Err(err);
+    }
+    
+    let kicked_ids: Vec<i32> = replaced.iter()
+        .chain(remaining.iter().take(overflow))
+        .map(|session| session.id)
+        .collect();
+    
+    // 踢掉旧的在线会话（其刷新令牌随会话级联删除）
+    end_sessions(conn, &kicked_ids, SESSION_END_KICKED)?;
+    
+    let access_token = generate_access_token(user.id, &user.username, config)?;
+    
+    // 记录登录日志
+    log_login_attempt(conn, Some(user.id), &user.username, device, None)?;
     
     // 记录新的在线会话
-    diesel::insert_into(online_users::table)
+    let session = diesel::insert_into(online_users::table)
         .values((
             online_users::user_id.eq(user.id),
             online_users::session_token.eq(&access_token),
             online_users::login_time.eq(Utc::now()),
-            online_users::hardware_code.eq(&req.hardware_code),
-            online_users::software_version.eq(&req.software_version),
-            online_users::ip_address.eq(ip),
+            online_users::hardware_code.eq(device.hardware_code),
+            online_users::software_version.eq(device.software_version),
+            online_users::ip_address.eq(device.ip),
             online_users::last_activity_at.eq(Utc::now()),
-            online_users::status_interval.eq(10), // 默认10分钟上传一次状态
+            online_users::status_interval.eq(config.heartbeat_interval.as_secs().clamp(1, i32::MAX as u64) as i32),
             online_users::created_at.eq(Utc::now()),
         ))
-        .execute(&mut conn)?;
+        .get_result::<OnlineUser>(conn)?;
+    
+    // 为新会话签发刷新令牌
+    let refresh_token = issue_refresh_token(conn, user, session.id, config)?;
     
     // 更新用户最后登录信息
     let updated_user = diesel::update(users::table.find(user.id))
         .set((
             users::last_login_at.eq(Utc::now()),
-            users::last_login_hardware.eq(&req.hardware_code),
-            users::last_login_version.eq(&req.software_version),
-            users::last_login_ip.eq(ip),
+            users::last_login_hardware.eq(device.hardware_code),
+            users::last_login_version.eq(device.software_version),
+            users::last_login_ip.eq(device.ip),
             users::updated_at.eq(Utc::now()),
         ))
-        .get_result::<User>(&mut conn)?;
+        .get_result::<User>(conn)?;
     
-    Ok((updated_user, access_token))
+    Ok((updated_user, SessionTokens { access_token, refresh_token, status_interval: session.status_interval }))
 }
 
 pub async fn logout_user(pool: &Pool, session_token: &str) -> Result<()> {
@@ -169,9 +619,7 @@ pub async fn logout_user(pool: &Pool, session_token: &str) -> Result<()> {
     let online_user = online_user.unwrap();
     
     // 删除在线会话
-    diesel::delete(online_users::table)
-        .filter(online_users::id.eq(online_user.id))
-        .execute(&mut conn)?;
+    end_sessions(&mut conn, &[online_user.id], SESSION_END_LOGOUT)?;
     
     // 更新用户的最后退出时间
     diesel::update(users::table.find(online_user.user_id))
@@ -204,35 +652,97 @@ pub async fn get_online_user_by_token(pool: &Pool, session_token: &str) -> Resul
     Ok(online_user)
 }
 
+/// 校验访问令牌对应的服务端会话
+///
+/// 令牌必须仍对应一条在线会话记录；被踢下线、已登出或重置密码后删除的会话立即失效。
+/// 如果会话的用户名、硬件码或IP已被加入黑名单，会话会被直接删除。
+pub async fn validate_session(pool: &Pool, session_token: &str, config: &Config) -> Result<OnlineUser> {
+    let mut conn = pool.get()?;
+    
+    let mut online_user = online_users::table
+        .filter(online_users::session_token.eq(session_token))
+        .first::<OnlineUser>(&mut conn)
+        .optional()?
+        .ok_or_else(|| AppError::Unauthorized("Session has been revoked".to_string()))?;
+    
+    // 超时未上报心跳的会话在清理任务执行前同样视为失效，最后活动时间以缓冲中尚未写回的心跳为准
+    HEARTBEAT_BUFFER.apply_to(&mut online_user);
+    if online_user.is_expired(config.heartbeat_timeout_multiplier) {
+        return Err(AppError::Unauthorized("Session has expired".to_string()));
+    }
+    
+    let user = users::table
+        .find(online_user.user_id)
+        .first::<User>(&mut conn)?;
+    
+    // 检查黑名单，被封禁的会话立即结束
+    if find_blacklist_entry(&mut conn, &user.username, &online_user.hardware_code, &online_user.ip_address)?.is_some() {
+        end_sessions(&mut conn, &[online_user.id], SESSION_END_BANNED)?;
+        return Err(AppError::Unauthorized("Device exception, cannot communicate".to_string()));
+    }
+    
+    Ok(online_user)
+}
+
 /// 刷新访问令牌
-pub async fn refresh_access_token(pool: &Pool, refresh_token: &str, config: &Config) -> Result<(User, String)> {
+///
+/// 每次刷新都会轮换刷新令牌：旧令牌被标记为已撤销，并签发新的访问令牌和刷新令牌。
+/// 如果已撤销的刷新令牌被再次使用，说明令牌可能已泄露，此时撤销整个令牌族（即结束对应会话）。
+pub async fn refresh_access_token(pool: &Pool, refresh_token: &str, config: &Config) -> Result<(User, SessionTokens)> {
     let mut conn = pool.get()?;
     
     // 验证刷新令牌
     let claims = verify_refresh_token(refresh_token, config)?;
     let user_id = claims.sub.parse::<i32>()?;
     
+    // 查找刷新令牌记录
+    let stored_token = refresh_tokens::table
+        .filter(refresh_tokens::token_hash.eq(sha256_hex(refresh_token)))
+        .filter(refresh_tokens::user_id.eq(user_id))
+        .first::<RefreshToken>(&mut conn)
+        .optional()?
+        .ok_or_else(|| AppError::Unauthorized("Invalid refresh token".to_string()))?;
+    
+    if stored_token.expires_at <= Utc::now() {
+        return Err(AppError::Unauthorized("Refresh token has expired".to_string()));
+    }
+    
+    // 原子地撤销当前令牌，撤销失败说明该令牌已经被使用过
+    let revoked_rows = diesel::update(refresh_tokens::table.find(stored_token.id))
+        .filter(refresh_tokens::revoked_at.is_null())
+        .set(refresh_tokens::revoked_at.eq(Utc::now()))
+        .execute(&mut conn)?;
+    
+    if revoked_rows == 0 {
+        warn!("Refresh token reuse detected for user {}, revoking session {}", user_id, stored_token.session_id);
+        
+        // 删除会话，同一令牌族下的所有刷新令牌随之级联删除
+        end_sessions(&mut conn, &[stored_token.session_id], SESSION_END_KICKED)?;
+        
+        return Err(AppError::Unauthorized("Refresh token reuse detected, session revoked".to_string()));
+    }
+    
     // 查找用户
     let user = users::table
         .find(user_id)
         .first::<User>(&mut conn)?;
     
-    // 查找在线用户记录
-    let online_user = online_users::table
-        .filter(online_users::user_id.eq(user_id))
-        .first::<OnlineUser>(&mut conn)?;
-    
-    // 生成新的访问令牌
+    // 生成新的访问令牌和刷新令牌
     let new_access_token = generate_access_token(user.id, &user.username, config)?;
+    let new_refresh_token = issue_refresh_token(&mut conn, &user, stored_token.session_id, config)?;
     
     // 更新在线用户记录中的访问令牌
-    diesel::update(online_users::table.find(online_user.id))
+    let session = diesel::update(online_users::table.find(stored_token.session_id))
         .set((
             online_users::session_token.eq(&new_access_token),
         ))
-        .execute(&mut conn)?;
+        .get_result::<OnlineUser>(&mut conn)?;
     
-    Ok((user, new_access_token))
+    Ok((user, SessionTokens {
+        access_token: new_access_token,
+        refresh_token: new_refresh_token,
+        status_interval: session.status_interval,
+    }))
 }
 
 /// 处理密码重置请求
@@ -243,49 +753,25 @@ pub async fn request_password_reset(pool: &Pool, req: ResetPasswordRequest, conf
     let user = users::table
         .filter(users::email.eq(&req.email))
         .first::<User>(&mut conn)?;
+    
+    // 邮箱未验证时不允许通过邮箱重置密码
+    ensure_email_verified(&user, VERIFIED_EMAIL_ACTION_PASSWORD_RESET, config)?;
+    
+    // 检查发送频率限制，避免通过重复申请绕过验证码错误次数上限或向邮箱反复发信
+    let remaining = email_cooldown_remaining(&mut conn, user.id, EMAIL_PURPOSE_PASSWORD_RESET, config)?;
+    if remaining > 0 {
+        return Err(AppError::TooManyRequests(format!("Please wait {} seconds before requesting another email", remaining)));
+    }
 
-    // 生成验证码
+    // 生成验证码和重置令牌，之前未使用的重置验证码同时作废
     let code = crate::utils::email::generate_verification_code();
     let token = crate::utils::jwt::generate_reset_token(user.id, &user.email, config)?;
-    let expires_at = Utc::now() + Duration::minutes(30); // 验证码30分钟内有效
-    
-    // 检查是否已经存在未使用的验证码记录
-    let existing_code = verification_codes::table
-        .filter(verification_codes::user_id.eq(user.id))
-        .filter(verification_codes::used.eq(false))
-        .first::<crate::database::verification_code::VerificationCode>(&mut conn)
-        .optional()?;
+    store_verification_code(&mut conn, &user, EMAIL_PURPOSE_PASSWORD_RESET, &code, &token, Duration::minutes(30), config)?; // 验证码30分钟内有效
     
-    match existing_code {
-        Some(vc) => {
-            // 更新现有记录
-            diesel::update(verification_codes::table.find(vc.id))
-                .set((
-                    verification_codes::code.eq(&code),
-                    verification_codes::token.eq(&token),
-                    verification_codes::expires_at.eq(expires_at),
-                    verification_codes::used.eq(false),
-                ))
-                .execute(&mut conn)?;
-        },
-        None => {
-            // 创建新记录
-            diesel::insert_into(verification_codes::table)
-                .values((
-                    verification_codes::user_id.eq(user.id),
-                    verification_codes::email.eq(&user.email),
-                    verification_codes::code.eq(&code),
-                    verification_codes::token.eq(&token),
-                    verification_codes::expires_at.eq(expires_at),
-                    verification_codes::used.eq(false),
-                    verification_codes::created_at.eq(Utc::now()),
-                ))
-                .execute(&mut conn)?;
-        }
-    }
+    record_email_dispatch(&mut conn, user.id, EMAIL_PURPOSE_PASSWORD_RESET, &user.email)?;
     
     // 发送密码重置邮件
-    crate::services::email::send_password_reset_email(pool, &user, &code, config).await?;
+    crate::services::email::send_password_reset_email(&user, &code, &token, config).await?;
     
     Ok(())
 }
@@ -300,39 +786,58 @@ pub async fn verify_reset_password(pool: &Pool, req: VerifyResetPasswordRequest,
         .filter(users::email.eq(&req.email))
         .first::<User>(&mut conn)?;
     
-    // 查找验证码记录
-    let verification_code = verification_codes::table
-        .filter(verification_codes::user_id.eq(user.id))
-        .filter(verification_codes::code.eq(&req.code))
-        .first::<crate::database::verification_code::VerificationCode>(&mut conn)
-        .optional()?;
-    
-    // 验证验证码是否存在
-    let verification_code = match verification_code {
-        Some(vc) => vc,
-        None => {
-            return Err(AppError::BadRequest("Invalid verification code".to_string()));
-        }
-    };
+    // 查找当前有效的重置验证码
+    let verification_code = find_active_verification_code(&mut conn, user.id, EMAIL_PURPOSE_PASSWORD_RESET)?
+        .ok_or_else(|| AppError::BadRequest("Verification code has expired or has already been used".to_string()))?;
     
-    // 验证验证码
-    if verification_code.used {
-        return Err(AppError::BadRequest("Verification code has already been used".to_string()));
+    // 验证码错误时累计失败次数，达到上限后作废
+    if !verification_code_matches(&verification_code.code, &req.code, config) {
+        return Err(record_verification_failure(&mut conn, &verification_code, config)?);
     }
     
-    if verification_code.expires_at <= Utc::now() {
-        return Err(AppError::BadRequest("Verification code has expired".to_string()));
-    }
+    complete_password_reset(&mut conn, &user, &verification_code, &req.new_password, config)
+}
+
+/// 通过邮件中的重置链接（重置令牌）设置新密码，无需提供用户名、邮箱和验证码
+pub async fn reset_password_with_token(pool: &Pool, req: ResetPasswordWithTokenRequest, config: &Config) -> Result<()> {
+    let mut conn = pool.get()?;
     
+    let claims = verify_reset_token(&req.token, config)
+        .map_err(|_| AppError::BadRequest("Invalid or expired reset link".to_string()))?;
+    let user_id = claims.sub.parse::<i32>()?;
+    
+    let user = users::table
+        .find(user_id)
+        .first::<User>(&mut conn)?;
+    
+    // 令牌必须对应当前有效的重置记录，使用后或重新申请后即失效
+    let verification_code = find_active_verification_code(&mut conn, user.id, EMAIL_PURPOSE_PASSWORD_RESET)?
+        .filter(|vc| constant_time_eq(&vc.token, &sha256_hex(&req.token)))
+        .ok_or_else(|| AppError::BadRequest("Invalid or expired reset link".to_string()))?;
+    
+    complete_password_reset(&mut conn, &user, &verification_code, &req.new_password, config)
+}
+
+/// 校验新密码并完成重置：作废验证码、更新密码、结束所有会话并解除登录锁定
+fn complete_password_reset(
+    conn: &mut PgConnection,
+    user: &User,
+    verification_code: &crate::database::verification_code::VerificationCode,
+    new_password: &str,
+    config: &Config,
+) -> Result<()> {
     // 先验证密码强度（在标记验证码为已使用之前）
-    let password_hash = hash_password(&req.new_password, config)?;
+    let password_hash = hash_password(new_password, &[&user.username, &user.email], config)?;
     
-    // 更新验证码为已使用
-    diesel::update(verification_codes::table.find(verification_code.id))
-        .set((
-            verification_codes::used.eq(true),
-        ))
-        .execute(&mut conn)?;
+    // 更新验证码为已使用，并发请求中只有一个能成功
+    let updated_rows = diesel::update(verification_codes::table.find(verification_code.id))
+        .filter(verification_codes::used.eq(false))
+        .set(verification_codes::used.eq(true))
+        .execute(conn)?;
+    
+    if updated_rows == 0 {
+        return Err(AppError::BadRequest("Verification code has already been used".to_string()));
+    }
     
     // 更新用户密码
     diesel::update(users::table.find(user.id))
@@ -340,7 +845,89 @@ pub async fn verify_reset_password(pool: &Pool, req: VerifyResetPasswordRequest,
             users::password_hash.eq(&password_hash),
             users::updated_at.eq(Utc::now()),
         ))
-        .execute(&mut conn)?;
+        .execute(conn)?;
+    
+    // 密码重置后结束该用户的所有在线会话
+    let session_ids = online_users::table
+        .filter(online_users::user_id.eq(user.id))
+        .select(online_users::id)
+        .load::<i32>(conn)?;
+    end_sessions(conn, &session_ids, SESSION_END_KICKED)?;
+    
+    // 通过密码重置流程解除该用户名的登录锁定
+    reset_login_failures(conn, &user.username)?;
+    
+    Ok(())
+}
+
+/// 已登录用户修改密码
+///
+/// 需要验证当前密码，修改成功后结束该用户除当前会话外的所有会话（刷新令牌随会话一并删除）
+pub async fn change_password(pool: &Pool, session: &OnlineUser, req: ChangePasswordRequest, config: &Config) -> Result<()> {
+    let mut conn = pool.get()?;
+    
+    let user = users::table
+        .find(session.user_id)
+        .first::<User>(&mut conn)?;
+    
+    // 验证当前密码
+    if !verify_password(&req.current_password, &user.password_hash)? {
+        return Err(AppError::Unauthorized("Invalid password".to_string()));
+    }
+    
+    if req.new_password == req.current_password {
+        return Err(AppError::BadRequest("New password must be different from the current password".to_string()));
+    }
+    
+    // 检查新密码强度并加密
+    let password_hash = hash_password(&req.new_password, &[&user.username, &user.email], config)?;
+    
+    conn.transaction(|conn| {
+        diesel::update(users::table.find(user.id))
+            .set((
+                users::password_hash.eq(&password_hash),
+                users::updated_at.eq(Utc::now()),
+            ))
+            .execute(conn)?;
+        
+        // 结束其他设备上的会话，保留当前会话
+        let session_ids = online_users::table
+            .filter(online_users::user_id.eq(user.id))
+            .filter(online_users::id.ne(session.id))
+            .select(online_users::id)
+            .load::<i32>(conn)?;
+        end_sessions(conn, &session_ids, SESSION_END_KICKED)?;
+        
+        Ok::<_, AppError>(())
+    })
+}
+
+/// 结束用户在所有设备上的会话（包括当前会话），返回结束的会话数
+pub async fn logout_all_sessions(pool: &Pool, user_id: i32) -> Result<usize> {
+    let mut conn = pool.get()?;
+    
+    let session_ids = online_users::table
+        .filter(online_users::user_id.eq(user_id))
+        .select(online_users::id)
+        .load::<i32>(&mut conn)?;
+    let ended_sessions = end_sessions(&mut conn, &session_ids, SESSION_END_LOGOUT)?;
+    
+    Ok(ended_sessions.len())
+}
+
+/// 结束用户自己的指定会话（其刷新令牌随会话级联删除）
+pub async fn revoke_user_session(pool: &Pool, user_id: i32, session_id: i32) -> Result<()> {
+    let mut conn = pool.get()?;
+    
+    let session_id = online_users::table
+        .filter(online_users::id.eq(session_id))
+        .filter(online_users::user_id.eq(user_id))
+        .select(online_users::id)
+        .first::<i32>(&mut conn)
+        .optional()?
+        .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+    
+    end_sessions(&mut conn, &[session_id], SESSION_END_KICKED)?;
     
     Ok(())
 }
@@ -0,0 +1,318 @@
+use diesel::prelude::*;
+use chrono::{Utc, Duration};
+use crate::database::{models::*, Pool};
+use crate::schema::*;
+use crate::services::auth::get_vip_level_policy;
+use crate::services::heartbeat::flush_heartbeat_buffer;
+use crate::services::session_history::{SESSION_END_KICKED, end_sessions};
+use crate::config::Config;
+use crate::errors::AppError;
+
+type Result<T> = std::result::Result<T, AppError>;
+
+/// 指纹变更来源：登录
+pub const FINGERPRINT_SOURCE_LOGIN: &str = "login";
+/// 指纹变更来源：心跳
+pub const FINGERPRINT_SOURCE_HEARTBEAT: &str = "heartbeat";
+/// 硬件码变更在审计表中记录的组件名
+const HARDWARE_CODE_COMPONENT: &str = "hardware_code";
+/// 能区分同型号机器的指纹组件，模糊匹配时至少要有一个一致
+///
+/// CPU、主板、BIOS和显卡在同一批次装配的机器上往往完全相同，只靠这些组件匹配会让多台克隆机器共用一个绑定名额。
+const STRONG_COMPONENTS: [&str; 2] = ["disk", "mac"];
+
+/// 登录时绑定设备
+///
+/// 已绑定的设备只更新最后登录时间；硬件码变化但上报的指纹与某台已绑定设备至少有
+/// `fingerprint_match_threshold` 个组件一致（其中至少包含硬盘或网卡）时，视为同一台设备并更新其硬件码；
+/// 其余情况按新设备处理，未超出策略的绑定上限时自动绑定，超出上限时返回 Forbidden，用户需要先解绑其他设备。
+pub fn bind_device(
+    conn: &mut PgConnection,
+    user_id: i32,
+    hardware_code: &str,
+    fingerprint: Option<&HardwareFingerprint>,
+    policy: &VipLevelPolicy,
+    config: &Config,
+) -> Result<()> {
+    let existing = user_devices::table
+        .filter(user_devices::user_id.eq(user_id))
+        .filter(user_devices::hardware_code.eq(hardware_code))
+        .filter(user_devices::unbound_at.is_null())
+        .first::<UserDevice>(conn)
+        .optional()?;
+
+    if let Some(device) = existing {
+        diesel::update(user_devices::table.find(device.id))
+            .set(user_devices::last_login_at.eq(Utc::now()))
+            .execute(conn)?;
+        if let Some(fingerprint) = fingerprint {
+            sync_fingerprint(conn, device.id, fingerprint, FINGERPRINT_SOURCE_LOGIN)?;
+        }
+        return Ok(());
+    }
+
+    // 硬件码变化时按指纹组件模糊匹配已绑定的设备
+    if let Some(fingerprint) = fingerprint {
+        if let Some(device) = find_matching_device(conn, user_id, fingerprint, config)? {
+            diesel::update(user_devices::table.find(device.id))
+                .set((
+                    user_devices::hardware_code.eq(hardware_code),
+                    user_devices::last_login_at.eq(Utc::now()),
+                ))
+                .execute(conn)?;
+            // 该设备上仍在线的会话同步更新硬件码，解绑设备时才能一并结束
+            diesel::update(online_users::table)
+                .filter(online_users::user_id.eq(user_id))
+                .filter(online_users::hardware_code.eq(&device.hardware_code))
+                .set(online_users::hardware_code.eq(hardware_code))
+                .execute(conn)?;
+            record_fingerprint_change(conn, device.id, HARDWARE_CODE_COMPONENT, Some(&device.hardware_code), hardware_code, FINGERPRINT_SOURCE_LOGIN)?;
+            sync_fingerprint(conn, device.id, fingerprint, FINGERPRINT_SOURCE_LOGIN)?;
+            return Ok(());
+        }
+    }
+
+    // 检查绑定设备数量上限（0表示不限制）
+    if policy.max_devices > 0 {
+        let bound_devices = user_devices::table
+            .filter(user_devices::user_id.eq(user_id))
+            .filter(user_devices::unbound_at.is_null())
+            .count()
+            .get_result::<i64>(conn)?;
+
+        if bound_devices >= policy.max_devices as i64 {
+            return Err(AppError::Forbidden(format!(
+                "Maximum number of bound devices ({}) reached, please unbind a device first",
+                policy.max_devices
+            )));
+        }
+    }
+
+    let device = diesel::insert_into(user_devices::table)
+        .values((
+            user_devices::user_id.eq(user_id),
+            user_devices::hardware_code.eq(hardware_code),
+            user_devices::bound_at.eq(Utc::now()),
+            user_devices::last_login_at.eq(Utc::now()),
+            user_devices::created_at.eq(Utc::now()),
+        ))
+        .get_result::<UserDevice>(conn)?;
+
+    if let Some(fingerprint) = fingerprint {
+        sync_fingerprint(conn, device.id, fingerprint, FINGERPRINT_SOURCE_LOGIN)?;
+    }
+
+    Ok(())
+}
+
+/// 心跳时更新会话所在设备的指纹组件，硬件码未绑定时忽略
+pub fn sync_session_fingerprint(conn: &mut PgConnection, session: &OnlineUser, fingerprint: &HardwareFingerprint) -> Result<()> {
+    let device = user_devices::table
+        .filter(user_devices::user_id.eq(session.user_id))
+        .filter(user_devices::hardware_code.eq(&session.hardware_code))
+        .filter(user_devices::unbound_at.is_null())
+        .first::<UserDevice>(conn)
+        .optional()?;
+
+    if let Some(device) = device {
+        sync_fingerprint(conn, device.id, fingerprint, FINGERPRINT_SOURCE_HEARTBEAT)?;
+    }
+
+    Ok(())
+}
+
+/// 查找与上报指纹匹配组件数最多且达到阈值的已绑定设备（阈值为0时不做模糊匹配）
+///
+/// 一致的组件中必须包含至少一个 [`STRONG_COMPONENTS`]。
+fn find_matching_device(
+    conn: &mut PgConnection,
+    user_id: i32,
+    fingerprint: &HardwareFingerprint,
+    config: &Config,
+) -> Result<Option<UserDevice>> {
+    if config.fingerprint_match_threshold == 0 {
+        return Ok(None);
+    }
+
+    let devices = user_devices::table
+        .filter(user_devices::user_id.eq(user_id))
+        .filter(user_devices::unbound_at.is_null())
+        .load::<UserDevice>(conn)?;
+
+    let device_ids: Vec<i32> = devices.iter().map(|device| device.id).collect();
+    let stored_components = device_fingerprint_components::table
+        .filter(device_fingerprint_components::device_id.eq_any(&device_ids))
+        .load::<DeviceFingerprintComponent>(conn)?;
+
+    let components = fingerprint.components();
+    let best_match = devices.into_iter()
+        .map(|device| {
+            let matched: Vec<&str> = components.iter()
+                .filter(|(name, value)| stored_components.iter().any(|stored| {
+                    stored.device_id == device.id && stored.component == *name && stored.value == *value
+                }))
+                .map(|(name, _)| *name)
+                .collect();
+            (matched, device)
+        })
+        .filter(|(matched, _)| {
+            matched.len() >= config.fingerprint_match_threshold
+                && matched.iter().any(|name| STRONG_COMPONENTS.contains(name))
+        })
+        .max_by_key(|(matched, _)| matched.len())
+        .map(|(_, device)| device);
+
+    Ok(best_match)
+}
+
+/// 保存设备上报的指纹组件，已有组件的值发生变化时写入审计记录
+fn sync_fingerprint(conn: &mut PgConnection, device_id: i32, fingerprint: &HardwareFingerprint, source: &str) -> Result<()> {
+    let stored_components = device_fingerprint_components::table
+        .filter(device_fingerprint_components::device_id.eq(device_id))
+        .load::<DeviceFingerprintComponent>(conn)?;
+    // 首次上报指纹时只保存组件，不记录变更
+    let first_report = stored_components.is_empty();
+
+    for (name, value) in fingerprint.components() {
+        match stored_components.iter().find(|stored| stored.component == name) {
+            Some(stored) if stored.value == value => {}
+            Some(stored) => {
+                diesel::update(device_fingerprint_components::table.find(stored.id))
+                    .set((
+                        device_fingerprint_components::value.eq(value),
+                        device_fingerprint_components::updated_at.eq(Utc::now()),
+                    ))
+                    .execute(conn)?;
+                record_fingerprint_change(conn, device_id, name, Some(&stored.value), value, source)?;
+            }
+            None => {
+                diesel::insert_into(device_fingerprint_components::table)
+                    .values((
+                        device_fingerprint_components::device_id.eq(device_id),
+                        device_fingerprint_components::component.eq(name),
+                        device_fingerprint_components::value.eq(value),
+                        device_fingerprint_components::created_at.eq(Utc::now()),
+                        device_fingerprint_components::updated_at.eq(Utc::now()),
+                    ))
+                    .execute(conn)?;
+                if !first_report {
+                    record_fingerprint_change(conn, device_id, name, None, value, source)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 写入一条指纹变更审计记录
+fn record_fingerprint_change(
+    conn: &mut PgConnection,
+    device_id: i32,
+    component: &str,
+    old_value: Option<&str>,
+    new_value: &str,
+    source: &str,
+) -> Result<()> {
+    diesel::insert_into(device_fingerprint_changes::table)
+        .values((
+            device_fingerprint_changes::device_id.eq(device_id),
+            device_fingerprint_changes::component.eq(component),
+            device_fingerprint_changes::old_value.eq(old_value),
+            device_fingerprint_changes::new_value.eq(new_value),
+            device_fingerprint_changes::source.eq(source),
+            device_fingerprint_changes::created_at.eq(Utc::now()),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// 获取用户当前绑定的设备，按最后登录时间从新到旧排列
+pub async fn get_user_devices(pool: &Pool, user_id: i32) -> Result<Vec<UserDevice>> {
+    let mut conn = pool.get()?;
+
+    let devices = user_devices::table
+        .filter(user_devices::user_id.eq(user_id))
+        .filter(user_devices::unbound_at.is_null())
+        .order_by(user_devices::last_login_at.desc())
+        .load::<UserDevice>(&mut conn)?;
+
+    Ok(devices)
+}
+
+/// 用户自助解绑设备
+///
+/// 两次解绑之间需要间隔策略配置的冷却时间；策略配置了解绑扣时时，从剩余VIP时长中扣除。
+/// 解绑后该设备上的在线会话同时结束。返回更新后的用户信息和实际扣除的小时数。
+pub async fn unbind_device(pool: &Pool, user_id: i32, device_id: i32) -> Result<(User, i32)> {
+    // 先写回缓冲中的心跳，使心跳中刚切换到该设备的会话也能按硬件码查到
+    flush_heartbeat_buffer(pool).await?;
+
+    let mut conn = pool.get()?;
+
+    conn.transaction(|conn| {
+        // 锁定用户记录，避免并发解绑绕过冷却时间
+        let user = users::table
+            .find(user_id)
+            .for_update()
+            .first::<User>(conn)?;
+
+        let device = user_devices::table
+            .filter(user_devices::id.eq(device_id))
+            .filter(user_devices::user_id.eq(user_id))
+            .filter(user_devices::unbound_at.is_null())
+            .first::<UserDevice>(conn)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound("Device not found".to_string()))?;
+
+        let policy = get_vip_level_policy(conn, user.current_vip_level())?;
+
+        // 检查解绑冷却时间
+        let last_unbound_at = user_devices::table
+            .filter(user_devices::user_id.eq(user_id))
+            .select(diesel::dsl::max(user_devices::unbound_at))
+            .first::<Option<chrono::DateTime<Utc>>>(conn)?;
+
+        if let Some(last_unbound_at) = last_unbound_at {
+            let next_allowed_at = last_unbound_at + Duration::hours(policy.unbind_cooldown_hours as i64);
+            if next_allowed_at > Utc::now() {
+                return Err(AppError::TooManyRequests(format!(
+                    "Devices can only be unbound once every {} hours",
+                    policy.unbind_cooldown_hours
+                )));
+            }
+        }
+
+        diesel::update(user_devices::table.find(device.id))
+            .set(user_devices::unbound_at.eq(Utc::now()))
+            .execute(conn)?;
+
+        // 结束该设备上的在线会话（其刷新令牌随会话级联删除）
+        let session_ids = online_users::table
+            .filter(online_users::user_id.eq(user_id))
+            .filter(online_users::hardware_code.eq(&device.hardware_code))
+            .select(online_users::id)
+            .load::<i32>(conn)?;
+        end_sessions(conn, &session_ids, SESSION_END_KICKED)?;
+
+        // VIP有效期内按策略扣除解绑时长
+        let penalty_hours = match user.vip_expires_at {
+            Some(expires_at) if policy.unbind_penalty_hours > 0 && expires_at > Utc::now() => policy.unbind_penalty_hours,
+            _ => 0,
+        };
+
+        let user = if penalty_hours > 0 {
+            diesel::update(users::table.find(user_id))
+                .set((
+                    users::vip_expires_at.eq(user.vip_expires_at.map(|expires_at| expires_at - Duration::hours(penalty_hours as i64))),
+                    users::updated_at.eq(Utc::now()),
+                ))
+                .get_result::<User>(conn)?
+        } else {
+            user
+        };
+
+        Ok((user, penalty_hours))
+    })
+}
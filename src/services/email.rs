@@ -1,8 +1,10 @@
 use log::{info, error};
 use crate::errors::AppError;
 use crate::utils::email::generate_verification_code;
-use crate::database::{models::User, Pool, verification_code::VerificationCode};
-use crate::schema::verification_codes;
+use crate::database::{models::{User, EmailChangeRequest, ChangeEmailRequest, EmailDispatchLog}, Pool, verification_code::VerificationCode};
+use crate::schema::{verification_codes, email_change_requests, email_dispatch_logs, users};
+use crate::services::auth::ensure_email_available;
+use crate::utils::crypto::{constant_time_eq, generate_random_token, hmac_sha256_hex, sha256_hex, verify_password};
 use crate::config::Config;
 use diesel::prelude::*;
 use chrono::{Utc, Duration};
@@ -12,55 +14,97 @@ use lettre::transport::smtp::authentication::Credentials;
 
 type Result<T> = std::result::Result<T, AppError>;
 
+/// 邮件用途：邮箱验证
+pub const EMAIL_PURPOSE_VERIFICATION: &str = "email_verification";
+/// 邮件用途：密码重置
+pub const EMAIL_PURPOSE_PASSWORD_RESET: &str = "password_reset";
+/// 邮件用途：免密登录链接
+pub const EMAIL_PURPOSE_MAGIC_LINK: &str = "magic_link";
+/// 邮件用途：修改邮箱
+pub const EMAIL_PURPOSE_EMAIL_CHANGE: &str = "email_change";
+
+/// 计算验证码的存储哈希，数据库中不保存明文验证码
+pub fn hash_verification_code(code: &str, config: &Config) -> String {
+    hmac_sha256_hex(&config.jwt_secret, code.trim())
+}
+
+/// 校验提交的验证码是否与存储的哈希一致
+pub fn verification_code_matches(stored_hash: &str, code: &str, config: &Config) -> bool {
+    constant_time_eq(stored_hash, &hash_verification_code(code, config))
+}
+
+/// 重新发送验证邮件的结果
+pub enum ResendOutcome {
+    /// 已发送，返回新的激活令牌和下次可发送前需要等待的秒数
+    Sent(String, i64),
+    /// 仍在冷却中，返回剩余秒数
+    CoolingDown(i64),
+    /// 邮箱未注册，为避免泄露账号是否存在，按已发送处理
+    NotRegistered,
+}
+
+/// 距离允许再次发送指定用途的邮件还需等待的秒数，为0表示可以发送
+///
+/// 24小时内发送次数达到上限时返回 TooManyRequests
+pub fn email_cooldown_remaining(conn: &mut PgConnection, user_id: i32, purpose: &str, config: &Config) -> Result<i64> {
+    let sent_today = email_dispatch_logs::table
+        .filter(email_dispatch_logs::user_id.eq(user_id))
+        .filter(email_dispatch_logs::purpose.eq(purpose))
+        .filter(email_dispatch_logs::created_at.gt(Utc::now() - Duration::days(1)))
+        .count()
+        .get_result::<i64>(conn)?;
+    
+    if sent_today >= config.email_daily_limit {
+        return Err(AppError::TooManyRequests("Daily email limit reached, please try again tomorrow".to_string()));
+    }
+    
+    let last_sent = email_dispatch_logs::table
+        .filter(email_dispatch_logs::user_id.eq(user_id))
+        .filter(email_dispatch_logs::purpose.eq(purpose))
+        .order_by(email_dispatch_logs::created_at.desc())
+        .first::<EmailDispatchLog>(conn)
+        .optional()?;
+    
+    let cooldown = config.email_resend_cooldown.as_secs() as i64;
+    let remaining = match last_sent {
+        Some(log) => (log.created_at + Duration::seconds(cooldown) - Utc::now()).num_seconds().max(0),
+        None => 0,
+    };
+    
+    Ok(remaining)
+}
+
+/// 记录一次邮件发送
+pub fn record_email_dispatch(conn: &mut PgConnection, user_id: i32, purpose: &str, email: &str) -> Result<()> {
+    diesel::insert_into(email_dispatch_logs::table)
+        .values((
+            email_dispatch_logs::user_id.eq(user_id),
+            email_dispatch_logs::purpose.eq(purpose),
+            email_dispatch_logs::email.eq(email),
+            email_dispatch_logs::created_at.eq(Utc::now()),
+        ))
+        .execute(conn)?;
+    
+    Ok(())
+}
+
 /// 发送邮箱验证码
+///
+/// 受冷却时间和每日发送上限限制，冷却中或达到上限时返回 TooManyRequests
 pub async fn send_verification_email(pool: &Pool, user: &User, config: &Config) -> Result<String> {
     let mut conn = pool.get()?;
     
-    // 检查是否已经存在未使用且未过期的验证码记录
-    let existing_code = verification_codes::table
-        .filter(verification_codes::user_id.eq(user.id))
-        .filter(verification_codes::used.eq(false))
-        .filter(verification_codes::expires_at.gt(Utc::now()))
-        .first::<VerificationCode>(&mut conn)
-        .optional()?;
+    // 检查发送频率限制
+    let remaining = email_cooldown_remaining(&mut conn, user.id, EMAIL_PURPOSE_VERIFICATION, config)?;
+    if remaining > 0 {
+        return Err(AppError::TooManyRequests(format!("Please wait {} seconds before requesting another email", remaining)));
+    }
     
-    let (code, token, expires_at) = match existing_code {
-        Some(vc) => {
-            // 已经存在有效验证码，只更新token
-            let new_token = crate::utils::jwt::generate_activation_token(user.id, &user.email, config)?;
-            
-            // 更新现有记录的token和过期时间
-            let new_expires_at = Utc::now() + Duration::minutes(30);
-            diesel::update(verification_codes::table.find(vc.id))
-                .set((
-                    verification_codes::token.eq(&new_token),
-                    verification_codes::expires_at.eq(new_expires_at),
-                ))
-                .execute(&mut conn)?;
-            
-            (vc.code, new_token, new_expires_at)
-        },
-        None => {
-            // 不存在有效验证码，创建新记录
-            let new_code = generate_verification_code();
-            let new_token = crate::utils::jwt::generate_activation_token(user.id, &user.email, config)?;
-            let new_expires_at = Utc::now() + Duration::minutes(30);
-            
-            diesel::insert_into(verification_codes::table)
-                .values((
-                    verification_codes::user_id.eq(user.id),
-                    verification_codes::email.eq(&user.email),
-                    verification_codes::code.eq(&new_code),
-                    verification_codes::token.eq(&new_token),
-                    verification_codes::expires_at.eq(new_expires_at),
-                    verification_codes::used.eq(false),
-                    verification_codes::created_at.eq(Utc::now()),
-                ))
-                .execute(&mut conn)?;
-            
-            (new_code, new_token, new_expires_at)
-        }
-    };
+    let code = generate_verification_code();
+    let token = crate::utils::jwt::generate_activation_token(user.id, &user.email, config)?;
+    store_verification_code(&mut conn, user, EMAIL_PURPOSE_VERIFICATION, &code, &token, Duration::minutes(30), config)?;
+    
+    record_email_dispatch(&mut conn, user.id, EMAIL_PURPOSE_VERIFICATION, &user.email)?;
     
     // 构建邮件内容 - 使用配置中的模板
     let subject = &config.email_verification_subject;
@@ -84,62 +128,182 @@ pub async fn send_verification_email(pool: &Pool, user: &User, config: &Config)
     Ok(token)
 }
 
-/// 验证邮箱验证码
-pub async fn verify_email_code(pool: &Pool, user_id: i32, code: &str) -> Result<()> {
-    let mut conn = pool.get()?;
+/// 保存新的验证码，同一用途下之前未使用的验证码同时作废
+///
+/// 验证码以HMAC哈希存储，令牌以SHA-256哈希存储，明文只出现在发给用户的邮件中
+pub fn store_verification_code(
+    conn: &mut PgConnection,
+    user: &User,
+    purpose: &str,
+    code: &str,
+    token: &str,
+    ttl: Duration,
+    config: &Config,
+) -> Result<()> {
+    diesel::update(verification_codes::table)
+        .filter(verification_codes::user_id.eq(user.id))
+        .filter(verification_codes::purpose.eq(purpose))
+        .filter(verification_codes::used.eq(false))
+        .set(verification_codes::used.eq(true))
+        .execute(conn)?;
     
-    // 查找所有相关验证码（不考虑是否有效）
-    let verification_codes = verification_codes::table
+    diesel::insert_into(verification_codes::table)
+        .values((
+            verification_codes::user_id.eq(user.id),
+            verification_codes::email.eq(&user.email),
+            verification_codes::code.eq(hash_verification_code(code, config)),
+            verification_codes::token.eq(sha256_hex(token)),
+            verification_codes::expires_at.eq(Utc::now() + ttl),
+            verification_codes::used.eq(false),
+            verification_codes::purpose.eq(purpose),
+            verification_codes::created_at.eq(Utc::now()),
+        ))
+        .execute(conn)?;
+    
+    Ok(())
+}
+
+/// 查找用户指定用途下当前有效的验证码
+pub fn find_active_verification_code(conn: &mut PgConnection, user_id: i32, purpose: &str) -> Result<Option<VerificationCode>> {
+    let active_code = verification_codes::table
         .filter(verification_codes::user_id.eq(user_id))
-        .filter(verification_codes::code.eq(code))
-        .load::<VerificationCode>(&mut conn)?;
+        .filter(verification_codes::purpose.eq(purpose))
+        .filter(verification_codes::used.eq(false))
+        .filter(verification_codes::expires_at.gt(Utc::now()))
+        .order_by(verification_codes::created_at.desc())
+        .first::<VerificationCode>(conn)
+        .optional()?;
+    
+    Ok(active_code)
+}
+
+/// 记录一次验证码校验失败，达到上限后作废该验证码
+///
+/// 失败次数在数据库中原子累加，并发提交的错误验证码同样计数。作废时返回 TooManyRequests，否则返回 BadRequest
+pub fn record_verification_failure(conn: &mut PgConnection, vc: &VerificationCode, config: &Config) -> Result<AppError> {
+    let failed_attempts = diesel::update(verification_codes::table.find(vc.id))
+        .filter(verification_codes::used.eq(false))
+        .set(verification_codes::failed_attempts.eq(verification_codes::failed_attempts + 1))
+        .returning(verification_codes::failed_attempts)
+        .get_result::<i32>(conn)
+        .optional()?;
+    
+    let failed_attempts = match failed_attempts {
+        Some(failed_attempts) => failed_attempts,
+        None => return Ok(AppError::BadRequest("Verification code has expired or has already been used".to_string())),
+    };
+    
+    if failed_attempts >= config.email_code_max_attempts.max(1) {
+        diesel::update(verification_codes::table.find(vc.id))
+            .set(verification_codes::used.eq(true))
+            .execute(conn)?;
+        return Ok(AppError::TooManyRequests("Too many failed attempts, please request a new verification code".to_string()));
+    }
+    Ok(AppError::BadRequest("Invalid verification code".to_string()))
+}
+
+/// 重新发送邮箱验证码，通过激活令牌或邮箱定位用户
+pub async fn resend_verification_email(pool: &Pool, activation_token: Option<&str>, email: Option<&str>, config: &Config) -> Result<ResendOutcome> {
+    let mut conn = pool.get()?;
     
-    if verification_codes.is_empty() {
-        return Err(AppError::BadRequest("Invalid verification code".to_string()));
+    let user = match (activation_token, email) {
+        (Some(token), _) => {
+            let claims = crate::utils::jwt::verify_activation_token(token, config)?;
+            let user_id = claims.sub.parse::<i32>()?;
+            users::table
+                .find(user_id)
+                .first::<User>(&mut conn)?
+        },
+        (None, Some(email)) => match users::table
+            .filter(users::email.eq(email))
+            .first::<User>(&mut conn)
+            .optional()? {
+            Some(user) => user,
+            None => return Ok(ResendOutcome::NotRegistered),
+        },
+        (None, None) => return Err(AppError::BadRequest("Either activation_token or email must be provided".to_string())),
+    };
+    
+    if user.email_verified {
+        return Err(AppError::BadRequest("Email has already been verified".to_string()));
     }
     
-    // 检查验证码状态
-    for vc in verification_codes {
-        if vc.used {
-            return Err(AppError::BadRequest("Verification code has already been used".to_string()));
-        }
-        
-        if vc.expires_at <= Utc::now() {
-            return Err(AppError::BadRequest("Verification code has expired".to_string()));
+    let remaining = email_cooldown_remaining(&mut conn, user.id, EMAIL_PURPOSE_VERIFICATION, config)?;
+    if remaining > 0 {
+        return Ok(ResendOutcome::CoolingDown(remaining));
+    }
+    
+    let activation_token = send_verification_email(pool, &user, config).await?;
+    
+    Ok(ResendOutcome::Sent(activation_token, config.email_resend_cooldown.as_secs() as i64))
+}
+
+/// 验证邮箱验证码
+///
+/// 错误次数达到上限后当前验证码作废，需要重新发送
+pub async fn verify_email_code(pool: &Pool, user_id: i32, code: &str, config: &Config) -> Result<()> {
+    let mut conn = pool.get()?;
+    
+    // 查找当前有效的验证码
+    let vc = match find_active_verification_code(&mut conn, user_id, EMAIL_PURPOSE_VERIFICATION)? {
+        Some(vc) => vc,
+        None => {
+            // 没有有效验证码时，按提交的验证码给出具体原因
+            let submitted_code = verification_codes::table
+                .filter(verification_codes::user_id.eq(user_id))
+                .filter(verification_codes::purpose.eq(EMAIL_PURPOSE_VERIFICATION))
+                .filter(verification_codes::code.eq(hash_verification_code(code, config)))
+                .first::<VerificationCode>(&mut conn)
+                .optional()?;
+            
+            return match submitted_code {
+                Some(vc) if vc.used => Err(AppError::BadRequest("Verification code has already been used".to_string())),
+                Some(_) => Err(AppError::BadRequest("Verification code has expired".to_string())),
+                None => Err(AppError::BadRequest("Invalid verification code".to_string())),
+            };
         }
-        
-        // 找到有效的验证码，更新为已使用
-        diesel::update(verification_codes::table.find(vc.id))
-            .set((
-                verification_codes::used.eq(true),
-            ))
-            .execute(&mut conn)?;
-        
-        // 更新用户邮箱验证状态
-        use crate::schema::users;
-        diesel::update(users::table.find(user_id))
-            .set((
-                users::email_verified.eq(true),
-                users::updated_at.eq(Utc::now()),
-            ))
-            .execute(&mut conn)?;
-        
-        return Ok(());
+    };
+    
+    if !verification_code_matches(&vc.code, code, config) {
+        // 记录失败次数，达到上限后作废验证码
+        return Err(record_verification_failure(&mut conn, &vc, config)?);
     }
     
-    // 理论上不会执行到这里
-    return Err(AppError::BadRequest("Invalid verification code".to_string()));
+    // 找到有效的验证码，更新为已使用；期间因错误次数达到上限而作废的验证码不能再使用
+    let updated_rows = diesel::update(verification_codes::table.find(vc.id))
+        .filter(verification_codes::used.eq(false))
+        .set((
+            verification_codes::used.eq(true),
+        ))
+        .execute(&mut conn)?;
+    
+    if updated_rows == 0 {
+        return Err(AppError::BadRequest("Verification code has already been used".to_string()));
+    }
+    
+    // 更新用户邮箱验证状态
+    diesel::update(users::table.find(user_id))
+        .set((
+            users::email_verified.eq(true),
+            users::updated_at.eq(Utc::now()),
+        ))
+        .execute(&mut conn)?;
+    
+    Ok(())
 }
 
 /// 发送密码重置邮件
-pub async fn send_password_reset_email(pool: &Pool, user: &User, verification_code: &str, config: &Config) -> Result<()> {
-    let mut conn = pool.get()?;
+pub async fn send_password_reset_email(user: &User, verification_code: &str, reset_token: &str, config: &Config) -> Result<()> {
+    // 重置链接携带重置令牌，用户可直接通过链接设置新密码
+    let separator = if config.password_reset_link_url.contains('?') { '&' } else { '?' };
+    let reset_link = format!("{}{}token={}", config.password_reset_link_url, separator, reset_token);
     
     // 构建邮件内容 - 使用配置中的模板
     let subject = &config.password_reset_subject;
     let body = config.password_reset_template
         .replace("{username}", &user.username)
-        .replace("{code}", &verification_code)
+        .replace("{code}", verification_code)
+        .replace("{reset_link}", &reset_link)
         .replace("{expiry}", "30 minutes");
     
     // 发送真实邮件
@@ -157,6 +321,208 @@ pub async fn send_password_reset_email(pool: &Pool, user: &User, verification_co
     Ok(())
 }
 
+/// 申请修改邮箱
+///
+/// 验证码发送到新邮箱，同时向旧邮箱发送带取消链接的通知。确认之前账号邮箱和验证状态保持不变，
+/// 再次申请时之前未完成的请求自动作废。受冷却时间和每日发送上限限制。
+pub async fn request_email_change(pool: &Pool, user_id: i32, req: ChangeEmailRequest, config: &Config) -> Result<()> {
+    let mut conn = pool.get()?;
+    
+    let user = users::table
+        .find(user_id)
+        .first::<User>(&mut conn)?;
+    
+    if !verify_password(&req.password, &user.password_hash)? {
+        return Err(AppError::Unauthorized("Invalid password".to_string()));
+    }
+    
+    if req.new_email == user.email {
+        return Err(AppError::BadRequest("New email must be different from the current email".to_string()));
+    }
+    
+    // 与注册时相同的格式和唯一性检查
+    crate::utils::email::validate_email(&req.new_email)?;
+    ensure_email_available(&mut conn, &req.new_email)?;
+    
+    // 检查发送频率限制，避免通过重复申请绕过验证码错误次数上限或向邮箱反复发信
+    let remaining = email_cooldown_remaining(&mut conn, user.id, EMAIL_PURPOSE_EMAIL_CHANGE, config)?;
+    if remaining > 0 {
+        return Err(AppError::TooManyRequests(format!("Please wait {} seconds before requesting another email", remaining)));
+    }
+    
+    // 作废之前未完成的请求
+    diesel::update(email_change_requests::table)
+        .filter(email_change_requests::user_id.eq(user.id))
+        .filter(email_change_requests::confirmed_at.is_null())
+        .filter(email_change_requests::cancelled_at.is_null())
+        .set(email_change_requests::cancelled_at.eq(Utc::now()))
+        .execute(&mut conn)?;
+    
+    let code = generate_verification_code();
+    let cancel_token = generate_random_token(32);
+    
+    diesel::insert_into(email_change_requests::table)
+        .values((
+            email_change_requests::user_id.eq(user.id),
+            email_change_requests::old_email.eq(&user.email),
+            email_change_requests::new_email.eq(&req.new_email),
+            email_change_requests::code.eq(hash_verification_code(&code, config)),
+            email_change_requests::cancel_token_hash.eq(sha256_hex(&cancel_token)),
+            email_change_requests::expires_at.eq(Utc::now() + Duration::minutes(30)),
+            email_change_requests::created_at.eq(Utc::now()),
+        ))
+        .execute(&mut conn)?;
+    
+    record_email_dispatch(&mut conn, user.id, EMAIL_PURPOSE_EMAIL_CHANGE, &req.new_email)?;
+    
+    // 向新邮箱发送验证码
+    let body = config.email_change_template
+        .replace("{code}", &code)
+        .replace("{expiry}", "30 minutes")
+        .replace("{username}", &user.username);
+    if let Err(err) = send_email(&req.new_email, &config.email_change_subject, body, config).await {
+        error!("Failed to send email change code to {}: {}", req.new_email, err);
+    }
+    
+    // 向旧邮箱发送变更通知和取消链接
+    let cancel_url = format!("{}/api/auth/email-change/cancel?token={}", config.public_base_url.trim_end_matches('/'), cancel_token);
+    let body = config.email_change_notice_template
+        .replace("{new_email}", &req.new_email)
+        .replace("{cancel_url}", &cancel_url)
+        .replace("{expiry}", "30 minutes")
+        .replace("{username}", &user.username);
+    if let Err(err) = send_email(&user.email, &config.email_change_notice_subject, body, config).await {
+        error!("Failed to send email change notice to {}: {}", user.email, err);
+    }
+    
+    info!("User {} requested email change to {}", user.id, req.new_email);
+    
+    Ok(())
+}
+
+/// 使用新邮箱收到的验证码确认修改邮箱，确认后新邮箱视为已验证
+///
+/// 错误次数达到上限后该请求作废，需要重新申请
+pub async fn confirm_email_change(pool: &Pool, user_id: i32, code: &str, config: &Config) -> Result<User> {
+    let mut conn = pool.get()?;
+    
+    let change_request = email_change_requests::table
+        .filter(email_change_requests::user_id.eq(user_id))
+        .filter(email_change_requests::confirmed_at.is_null())
+        .filter(email_change_requests::cancelled_at.is_null())
+        .order_by(email_change_requests::created_at.desc())
+        .first::<EmailChangeRequest>(&mut conn)
+        .optional()?
+        .ok_or_else(|| AppError::BadRequest("No pending email change request".to_string()))?;
+    
+    if change_request.expires_at <= Utc::now() {
+        return Err(AppError::BadRequest("Verification code has expired".to_string()));
+    }
+    
+    if !verification_code_matches(&change_request.code, code, config) {
+        // 记录失败次数，达到上限后作废请求
+        return Err(record_email_change_failure(&mut conn, &change_request, config)?);
+    }
+    
+    conn.transaction(|conn| {
+        // 申请之后邮箱可能已被其他账号占用，确认时再检查一次
+        ensure_email_available(conn, &change_request.new_email)?;
+        
+        // 并发请求中只有一个能确认，期间已作废的请求不能再确认
+        let updated_rows = diesel::update(email_change_requests::table.find(change_request.id))
+            .filter(email_change_requests::confirmed_at.is_null())
+            .filter(email_change_requests::cancelled_at.is_null())
+            .set(email_change_requests::confirmed_at.eq(Utc::now()))
+            .execute(conn)?;
+        
+        if updated_rows == 0 {
+            return Err(AppError::BadRequest("No pending email change request".to_string()));
+        }
+        
+        let user = diesel::update(users::table.find(user_id))
+            .set((
+                users::email.eq(&change_request.new_email),
+                users::email_verified.eq(true),
+                users::updated_at.eq(Utc::now()),
+            ))
+            .get_result::<User>(conn)?;
+        
+        info!("User {} changed email from {} to {}", user_id, change_request.old_email, change_request.new_email);
+        
+        Ok(user)
+    })
+}
+
+/// 记录一次邮箱变更验证码校验失败，达到上限后作废该请求
+///
+/// 失败次数在数据库中原子累加，并发提交的错误验证码同样计数。作废时返回 TooManyRequests，否则返回 BadRequest
+fn record_email_change_failure(conn: &mut PgConnection, change_request: &EmailChangeRequest, config: &Config) -> Result<AppError> {
+    let failed_attempts = diesel::update(email_change_requests::table.find(change_request.id))
+        .filter(email_change_requests::confirmed_at.is_null())
+        .filter(email_change_requests::cancelled_at.is_null())
+        .set(email_change_requests::failed_attempts.eq(email_change_requests::failed_attempts + 1))
+        .returning(email_change_requests::failed_attempts)
+        .get_result::<i32>(conn)
+        .optional()?;
+    
+    let failed_attempts = match failed_attempts {
+        Some(failed_attempts) => failed_attempts,
+        None => return Ok(AppError::BadRequest("No pending email change request".to_string())),
+    };
+    
+    if failed_attempts >= config.email_code_max_attempts.max(1) {
+        diesel::update(email_change_requests::table.find(change_request.id))
+            .filter(email_change_requests::cancelled_at.is_null())
+            .set(email_change_requests::cancelled_at.eq(Utc::now()))
+            .execute(conn)?;
+        return Ok(AppError::TooManyRequests("Too many failed attempts, please request a new email change".to_string()));
+    }
+    Ok(AppError::BadRequest("Invalid verification code".to_string()))
+}
+
+/// 通过旧邮箱收到的取消链接取消未完成的邮箱变更
+pub async fn cancel_email_change(pool: &Pool, cancel_token: &str) -> Result<()> {
+    let mut conn = pool.get()?;
+    
+    let change_request = email_change_requests::table
+        .filter(email_change_requests::cancel_token_hash.eq(sha256_hex(cancel_token)))
+        .first::<EmailChangeRequest>(&mut conn)
+        .optional()?
+        .ok_or_else(|| AppError::BadRequest("Invalid cancel token".to_string()))?;
+    
+    if change_request.confirmed_at.is_some() {
+        return Err(AppError::BadRequest("Email change has already been confirmed".to_string()));
+    }
+    
+    if change_request.cancelled_at.is_none() {
+        diesel::update(email_change_requests::table.find(change_request.id))
+            .set(email_change_requests::cancelled_at.eq(Utc::now()))
+            .execute(&mut conn)?;
+        
+        info!("User {} cancelled email change to {}", change_request.user_id, change_request.new_email);
+    }
+    
+    Ok(())
+}
+
+/// 发送免密登录链接邮件
+pub async fn send_magic_link_email(user: &User, token: &str, config: &Config) -> Result<()> {
+    let separator = if config.magic_link_url.contains('?') { '&' } else { '?' };
+    let login_link = format!("{}{}token={}", config.magic_link_url, separator, token);
+    
+    let body = config.magic_link_template
+        .replace("{username}", &user.username)
+        .replace("{login_link}", &login_link)
+        .replace("{expiry}", &format!("{} minutes", (config.magic_link_ttl.as_secs() / 60).max(1)));
+    
+    match send_email(&user.email, &config.magic_link_subject, body, config).await {
+        Ok(_) => info!("Magic login link sent to {}", user.email),
+        Err(err) => error!("Failed to send magic login link to {}: {}", user.email, err),
+    }
+    
+    Ok(())
+}
+
 /// 实际发送邮件的辅助函数
 async fn send_email(to: &str, subject: &str, body: String, config: &Config) -> Result<()> {
     // 创建邮件
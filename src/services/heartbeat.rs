@@ -1,42 +1,200 @@
 use diesel::prelude::*;
+use diesel::dsl::sql;
+use diesel::expression::SqlLiteral;
+use diesel::sql_types::{Array, Bool, Integer, Timestamptz, Varchar};
 use chrono::Utc;
+use log::info;
 use crate::database::{models::*, Pool};
 use crate::schema::*;
+use crate::services::device::sync_session_fingerprint;
+use crate::services::heartbeat_buffer::{HEARTBEAT_BUFFER, PendingHeartbeat};
+use crate::services::session_command::{COMMAND_FORCE_LOGOUT, acknowledge_session_commands, take_pending_session_commands};
+use crate::services::session_history::{SESSION_END_KICKED, SESSION_END_TIMEOUT, archive_sessions, end_sessions};
+use crate::config::Config;
 use crate::errors::AppError;
 
 type Result<T> = std::result::Result<T, AppError>;
 
-pub async fn update_heartbeat(pool: &Pool, session_token: &str, hardware_code: &str, software_version: &str) -> Result<()> {
+/// 心跳处理结果：下一次上报间隔和待处理的会话指令
+pub struct HeartbeatOutcome {
+    pub status_interval: i32,
+    pub commands: Vec<SessionCommand>,
+}
+
+/// 会话超时条件：超过 上报间隔（秒）× 倍数 未上报心跳
+///
+/// 倍数来自配置而非客户端输入，直接拼入SQL。
+fn session_expired(timeout_multiplier: i32) -> SqlLiteral<Bool> {
+    sql::<Bool>(&format!(
+        "online_users.last_activity_at + make_interval(secs => online_users.status_interval * {}) < NOW()",
+        timeout_multiplier
+    ))
+}
+
+pub async fn update_heartbeat(pool: &Pool, req: &HeartbeatRequest, config: &Config) -> Result<HeartbeatOutcome> {
     let mut conn = pool.get()?;
     
-    // 更新在线用户的最后活动时间
-    let updated_rows = diesel::update(online_users::table)
-        .filter(online_users::session_token.eq(session_token))
-        .set((
-            online_users::last_activity_at.eq(Utc::now()),
-            online_users::hardware_code.eq(hardware_code),
-            online_users::software_version.eq(software_version),
-        ))
-        .execute(&mut conn)?;
-    
-    // 检查是否有记录被更新，如果没有，说明token不存在
-    if updated_rows == 0 {
+    // 查找在线会话，如果没有，说明token不存在
+    let mut session = online_users::table
+        .filter(online_users::session_token.eq(&req.session_token))
+        .first::<OnlineUser>(&mut conn)
+        .optional()?
+        .ok_or_else(|| AppError::BadRequest("invalid token".to_string()))?;
+    
+    // 已超时的会话不再续期，最后活动时间以缓冲中尚未写回的心跳为准
+    HEARTBEAT_BUFFER.apply_to(&mut session);
+    if session.is_expired(config.heartbeat_timeout_multiplier) {
         return Err(AppError::BadRequest("invalid token".to_string()));
     }
     
-    Ok(())
+    // 心跳先写入缓冲，由后台任务批量写回数据库
+    session.last_activity_at = Utc::now();
+    session.hardware_code = req.hardware_code.clone();
+    session.software_version = req.software_version.clone();
+    HEARTBEAT_BUFFER.record(session.id, PendingHeartbeat {
+        last_activity_at: session.last_activity_at,
+        hardware_code: session.hardware_code.clone(),
+        software_version: session.software_version.clone(),
+    });
+    
+    // 记录设备指纹组件的变化
+    if let Some(fingerprint) = &req.fingerprint {
+        sync_session_fingerprint(&mut conn, &session, fingerprint)?;
+    }
+    
+    // 确认客户端已处理的指令，再取出仍待处理的指令
+    acknowledge_session_commands(&mut conn, session.id, &req.acknowledged_command_ids)?;
+    let commands = take_pending_session_commands(&mut conn, session.id)?;
+    
+    // 下发强制下线指令后结束该会话
+    if commands.iter().any(|command| command.command_type == COMMAND_FORCE_LOGOUT) {
+        end_sessions(&mut conn, &[session.id], SESSION_END_KICKED)?;
+    }
+    
+    Ok(HeartbeatOutcome {
+        status_interval: session.status_interval,
+        commands,
+    })
 }
 
-pub async fn cleanup_inactive_users(pool: &Pool, inactive_interval: i64) -> Result<()> {
+/// 刷新会话的最后活动时间（WebSocket推送连接存活时调用），会话已结束时返回 false
+pub async fn touch_session(pool: &Pool, session_id: i32, config: &Config) -> Result<bool> {
     let mut conn = pool.get()?;
     
-    // 计算不活跃时间阈值
-    let inactive_threshold = Utc::now() - chrono::Duration::minutes(inactive_interval as i64);
+    let mut session = match online_users::table.find(session_id).first::<OnlineUser>(&mut conn).optional()? {
+        Some(session) => session,
+        None => return Ok(false),
+    };
+    
+    // 与心跳接口一致：以缓冲中的心跳为准判断超时，刷新同样写入缓冲
+    HEARTBEAT_BUFFER.apply_to(&mut session);
+    if session.is_expired(config.heartbeat_timeout_multiplier) {
+        return Ok(false);
+    }
     
-    // 删除超过阈值的在线用户记录
-    diesel::delete(online_users::table)
-        .filter(online_users::last_activity_at.lt(inactive_threshold))
-        .execute(&mut conn)?;
+    HEARTBEAT_BUFFER.record(session.id, PendingHeartbeat {
+        last_activity_at: Utc::now(),
+        hardware_code: session.hardware_code,
+        software_version: session.software_version,
+    });
+    
+    Ok(true)
+}
+
+/// 结束被强制下线的会话
+pub async fn end_session(pool: &Pool, session_id: i32) -> Result<()> {
+    let mut conn = pool.get()?;
+    
+    end_sessions(&mut conn, &[session_id], SESSION_END_KICKED)?;
     
     Ok(())
 }
+
+/// 每批写回的心跳数
+const HEARTBEAT_FLUSH_BATCH_SIZE: usize = 1000;
+
+/// 批量写回心跳的语句
+const HEARTBEAT_FLUSH_SQL: &str = "UPDATE online_users AS o \
+    SET last_activity_at = b.last_activity_at, \
+        hardware_code = b.hardware_code, \
+        software_version = b.software_version \
+    FROM UNNEST($1, $2, $3, $4) AS b(id, last_activity_at, hardware_code, software_version) \
+    WHERE o.id = b.id";
+
+/// 将缓冲中的心跳批量写回数据库，返回写回的会话数
+///
+/// 已结束的会话对应的心跳直接丢弃。写回失败时心跳放回缓冲，下次重试。
+pub async fn flush_heartbeat_buffer(pool: &Pool) -> Result<usize> {
+    let heartbeats = HEARTBEAT_BUFFER.drain();
+    if heartbeats.is_empty() {
+        return Ok(0);
+    }
+    
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            HEARTBEAT_BUFFER.restore(heartbeats);
+            return Err(err.into());
+        }
+    };
+    
+    let entries: Vec<(&i32, &PendingHeartbeat)> = heartbeats.iter().collect();
+    for batch in entries.chunks(HEARTBEAT_FLUSH_BATCH_SIZE) {
+        let result = diesel::sql_query(HEARTBEAT_FLUSH_SQL)
+            .bind::<Array<Integer>, _>(batch.iter().map(|(session_id, _)| **session_id).collect::<Vec<_>>())
+            .bind::<Array<Timestamptz>, _>(batch.iter().map(|(_, heartbeat)| heartbeat.last_activity_at).collect::<Vec<_>>())
+            .bind::<Array<Varchar>, _>(batch.iter().map(|(_, heartbeat)| heartbeat.hardware_code.clone()).collect::<Vec<_>>())
+            .bind::<Array<Varchar>, _>(batch.iter().map(|(_, heartbeat)| heartbeat.software_version.clone()).collect::<Vec<_>>())
+            .execute(&mut conn);
+        
+        // 已写回的批次重复写入不影响结果，失败时整体放回
+        if let Err(err) = result {
+            HEARTBEAT_BUFFER.restore(heartbeats);
+            return Err(err.into());
+        }
+    }
+    
+    Ok(heartbeats.len())
+}
+
+/// 清理超时的在线会话，并为对应用户记录退出时间，返回清理的会话数
+///
+/// 每个会话按自己的上报间隔计算超时时间。清理前先写回缓冲中的心跳，避免误删刚上报过心跳的会话。
+pub async fn cleanup_inactive_users(pool: &Pool, timeout_multiplier: i32) -> Result<usize> {
+    flush_heartbeat_buffer(pool).await?;
+    
+    let mut conn = pool.get()?;
+    
+    conn.transaction(|conn| {
+        // 删除超时的在线会话（其刷新令牌和指令随会话级联删除），超时判断与删除在同一条语句中完成，
+        // 避免删除期间刚上报心跳的会话
+        let expired_sessions = diesel::delete(online_users::table)
+            .filter(session_expired(timeout_multiplier))
+            .get_results::<OnlineUser>(conn)?;
+        
+        if expired_sessions.is_empty() {
+            return Ok(0);
+        }
+        
+        archive_sessions(conn, &expired_sessions, SESSION_END_TIMEOUT)?;
+        
+        // 与主动登出一致，更新用户的最后退出时间
+        let user_ids: Vec<i32> = expired_sessions.iter().map(|session| session.user_id).collect();
+        diesel::update(users::table)
+            .filter(users::id.eq_any(&user_ids))
+            .set((
+                users::last_logout_at.eq(Utc::now()),
+                users::updated_at.eq(Utc::now()),
+            ))
+            .execute(conn)?;
+        
+        for session in &expired_sessions {
+            info!(
+                "Session {} of user {} timed out (last activity at {}, status interval {}s)",
+                session.id, session.user_id, session.last_activity_at, session.status_interval
+            );
+        }
+        
+        Ok(expired_sessions.len())
+    })
+}
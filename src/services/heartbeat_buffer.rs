@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use chrono::{DateTime, Utc};
+use crate::database::models::OnlineUser;
+
+/// 尚未写回数据库的心跳
+#[derive(Debug, Clone)]
+pub struct PendingHeartbeat {
+    pub last_activity_at: DateTime<Utc>,
+    pub hardware_code: String,
+    pub software_version: String,
+}
+
+/// 心跳写缓冲：按会话ID保存最近一次心跳，由后台任务定期批量写回 `online_users`
+///
+/// 仅保存在进程内存中。判断会话是否超时前需要先用 [`HeartbeatBuffer::apply_to`] 合并缓冲中的心跳。
+pub struct HeartbeatBuffer {
+    pending: Mutex<HashMap<i32, PendingHeartbeat>>,
+}
+
+/// 全局心跳写缓冲
+pub static HEARTBEAT_BUFFER: LazyLock<HeartbeatBuffer> = LazyLock::new(|| HeartbeatBuffer {
+    pending: Mutex::new(HashMap::new()),
+});
+
+impl HeartbeatBuffer {
+    /// 记录会话的一次心跳，同一会话只保留最新的一次
+    pub fn record(&self, session_id: i32, heartbeat: PendingHeartbeat) {
+        self.lock().insert(session_id, heartbeat);
+    }
+
+    /// 将缓冲中尚未写回的心跳合并到从数据库读出的会话记录上
+    pub fn apply_to(&self, session: &mut OnlineUser) {
+        if let Some(heartbeat) = self.lock().get(&session.id) {
+            if heartbeat.last_activity_at > session.last_activity_at {
+                session.last_activity_at = heartbeat.last_activity_at;
+                session.hardware_code = heartbeat.hardware_code.clone();
+                session.software_version = heartbeat.software_version.clone();
+            }
+        }
+    }
+
+    /// 取出全部待写回的心跳
+    pub fn drain(&self) -> HashMap<i32, PendingHeartbeat> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    /// 写回失败时放回心跳，期间收到的更新的心跳优先
+    pub fn restore(&self, heartbeats: HashMap<i32, PendingHeartbeat>) {
+        let mut pending = self.lock();
+        for (session_id, heartbeat) in heartbeats {
+            pending.entry(session_id).or_insert(heartbeat);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<i32, PendingHeartbeat>> {
+        self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
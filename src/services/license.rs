@@ -0,0 +1,62 @@
+use diesel::prelude::*;
+use chrono::{Utc, Duration, DateTime};
+use crate::database::{models::*, Pool};
+use crate::schema::*;
+use crate::config::Config;
+use crate::errors::AppError;
+use crate::utils::crypto::generate_random_token;
+use crate::utils::jwt::sign_claims;
+
+type Result<T> = std::result::Result<T, AppError>;
+
+/// 为当前会话的设备签发指定软件的离线许可证
+///
+/// 许可证绑定用户、硬件码、软件和VIP信息，离线可用时长为配置的宽限期，
+/// 付费软件的许可证不会晚于VIP到期时间失效
+pub async fn issue_license(pool: &Pool, session: &OnlineUser, software_id: i32, config: &Config) -> Result<(String, DateTime<Utc>)> {
+    // 离线许可证必须使用非对称密钥签名，否则客户端无法在本地验证
+    if config.jwt_keys.signing_key.is_none() {
+        return Err(AppError::InternalServerError("Offline license signing key is not configured".to_string()));
+    }
+
+    let mut conn = pool.get()?;
+
+    let user = users::table
+        .find(session.user_id)
+        .first::<User>(&mut conn)?;
+
+    let software = software::table
+        .find(software_id)
+        .first::<Software>(&mut conn)?;
+
+    // 检查是否有权限使用
+    let vip_level = user.current_vip_level();
+    if vip_level < software.required_vip_level {
+        return Err(AppError::Forbidden("VIP level is too low for this software".to_string()));
+    }
+
+    let now = Utc::now();
+    let grace = Duration::from_std(config.license_offline_grace).unwrap_or(Duration::days(3));
+    let mut expires_at = now + grace;
+    if software.required_vip_level > 0 {
+        if let Some(vip_expires_at) = user.vip_expires_at {
+            expires_at = expires_at.min(vip_expires_at);
+        }
+    }
+
+    let claims = LicenseClaims {
+        sub: user.id.to_string(),
+        token_type: "license".to_string(),
+        hardware_code: session.hardware_code.clone(),
+        software_id: software.id,
+        vip_level,
+        vip_expires_at: user.vip_expires_at.map(|expires_at| expires_at.timestamp()),
+        iat: now.timestamp(),
+        exp: expires_at.timestamp(),
+        jti: generate_random_token(16),
+    };
+
+    let license = sign_claims(&claims, config)?;
+
+    Ok((license, expires_at))
+}
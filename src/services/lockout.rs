@@ -0,0 +1,118 @@
+use diesel::prelude::*;
+use chrono::{Utc, Duration};
+use log::warn;
+use crate::database::models::LoginLockout;
+use crate::schema::login_lockouts;
+use crate::config::Config;
+use crate::errors::AppError;
+
+type Result<T> = std::result::Result<T, AppError>;
+
+/// 按用户名统计的锁定范围
+pub const SCOPE_USERNAME: &str = "username";
+/// 按IP统计的锁定范围
+pub const SCOPE_IP: &str = "ip";
+
+/// 检查用户名或IP是否处于锁定状态
+pub fn check_login_lockout(conn: &mut PgConnection, username: &str, ip: &str) -> Result<()> {
+    let active_lockout = login_lockouts::table
+        .filter(
+            login_lockouts::scope.eq(SCOPE_USERNAME).and(login_lockouts::scope_key.eq(username))
+            .or(login_lockouts::scope.eq(SCOPE_IP).and(login_lockouts::scope_key.eq(ip)))
+        )
+        .filter(login_lockouts::locked_until.gt(Utc::now()))
+        .order_by(login_lockouts::locked_until.desc())
+        .first::<LoginLockout>(conn)
+        .optional()?;
+
+    match active_lockout.and_then(|lockout| lockout.locked_until) {
+        Some(locked_until) => {
+            let remaining = (locked_until - Utc::now()).num_seconds().max(1);
+            Err(AppError::TooManyRequests(format!("Too many failed login attempts, try again in {} seconds", remaining)))
+        }
+        None => Ok(()),
+    }
+}
+
+/// 记录一次登录失败，同时累计用户名和IP两个范围的失败次数
+pub fn record_login_failure(conn: &mut PgConnection, username: &str, ip: &str, config: &Config) -> Result<()> {
+    record_scope_failure(conn, SCOPE_USERNAME, username, config)?;
+    record_scope_failure(conn, SCOPE_IP, ip, config)?;
+    Ok(())
+}
+
+/// 登录成功后清除用户名范围的失败计数
+///
+/// IP范围的计数不在此清除，避免攻击者用自己的账号登录来重置IP计数
+pub fn reset_login_failures(conn: &mut PgConnection, username: &str) -> Result<()> {
+    diesel::delete(login_lockouts::table)
+        .filter(login_lockouts::scope.eq(SCOPE_USERNAME))
+        .filter(login_lockouts::scope_key.eq(username))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// 在失败统计窗口内累计失败次数，达到上限时按递增时长锁定
+fn record_scope_failure(conn: &mut PgConnection, scope: &str, scope_key: &str, config: &Config) -> Result<()> {
+    conn.transaction(|conn| {
+        diesel::insert_into(login_lockouts::table)
+            .values((
+                login_lockouts::scope.eq(scope),
+                login_lockouts::scope_key.eq(scope_key),
+                login_lockouts::window_started_at.eq(Utc::now()),
+                login_lockouts::updated_at.eq(Utc::now()),
+            ))
+            .on_conflict((login_lockouts::scope, login_lockouts::scope_key))
+            .do_nothing()
+            .execute(conn)?;
+
+        let lockout = login_lockouts::table
+            .filter(login_lockouts::scope.eq(scope))
+            .filter(login_lockouts::scope_key.eq(scope_key))
+            .for_update()
+            .first::<LoginLockout>(conn)?;
+
+        let now = Utc::now();
+        let window = Duration::from_std(config.login_failure_window).unwrap_or(Duration::minutes(15));
+        let max_duration = Duration::from_std(config.login_lockout_max_duration).unwrap_or(Duration::days(1));
+
+        // 统计窗口过期后重新计数；长时间没有失败记录时，递增的锁定等级也一并清零
+        let (mut failure_count, mut window_started_at) = (lockout.failure_count, lockout.window_started_at);
+        if window_started_at + window <= now {
+            failure_count = 0;
+            window_started_at = now;
+        }
+        let mut lockout_count = if lockout.updated_at + max_duration <= now { 0 } else { lockout.lockout_count };
+        let mut locked_until = lockout.locked_until;
+
+        failure_count += 1;
+        if failure_count >= config.login_max_failures.max(1) {
+            lockout_count += 1;
+            let duration = lockout_duration(lockout_count, config);
+            locked_until = Some(now + duration);
+            failure_count = 0;
+            window_started_at = now;
+            warn!("Login locked for {} {} for {} seconds after repeated failures", scope, scope_key, duration.num_seconds());
+        }
+
+        diesel::update(login_lockouts::table.find(lockout.id))
+            .set((
+                login_lockouts::failure_count.eq(failure_count),
+                login_lockouts::window_started_at.eq(window_started_at),
+                login_lockouts::lockout_count.eq(lockout_count),
+                login_lockouts::locked_until.eq(locked_until),
+                login_lockouts::updated_at.eq(now),
+            ))
+            .execute(conn)?;
+
+        Ok(())
+    })
+}
+
+/// 计算第N次锁定的时长：首次为基础时长，之后每次翻倍，不超过最大锁定时长
+fn lockout_duration(lockout_count: i32, config: &Config) -> Duration {
+    let base_secs = config.login_lockout_duration.as_secs() as i64;
+    let max_secs = config.login_lockout_max_duration.as_secs() as i64;
+    let exponent = (lockout_count - 1).clamp(0, 20) as u32;
+    Duration::seconds(base_secs.saturating_mul(1i64 << exponent).min(max_secs))
+}
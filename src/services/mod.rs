@@ -1,6 +1,15 @@
+pub mod activation;
 pub mod auth;
+pub mod device;
 pub mod email;
 pub mod heartbeat;
+pub mod heartbeat_buffer;
+pub mod license;
+pub mod lockout;
+pub mod push;
 pub mod recharge;
+pub mod session_command;
+pub mod session_history;
 pub mod software;
+pub mod two_factor;
 pub mod user;
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use crate::database::models::SessionCommand;
+
+/// 推送给WebSocket连接的消息
+pub enum PushMessage {
+    /// 新的会话指令
+    Command(SessionCommand),
+    /// 会话已结束（登出、踢下线、超时、封禁），附带结束原因
+    SessionEnded(String),
+}
+
+/// 会话ID到其推送连接（连接ID, 发送端）的映射
+type Connections = HashMap<i32, Vec<(u64, UnboundedSender<PushMessage>)>>;
+
+/// 在线会话的WebSocket推送连接注册表
+///
+/// 仅保存在进程内存中，多实例部署时只能推送到连接在本实例上的客户端，
+/// 其他客户端仍通过心跳领取指令。
+pub struct PushHub {
+    connections: Mutex<Connections>,
+    next_connection_id: AtomicU64,
+}
+
+/// 全局推送注册表，业务代码写入指令后通过它即时推送
+pub static PUSH_HUB: LazyLock<PushHub> = LazyLock::new(|| PushHub {
+    connections: Mutex::new(HashMap::new()),
+    next_connection_id: AtomicU64::new(1),
+});
+
+impl PushHub {
+    /// 为会话注册一个推送连接，返回连接ID和接收推送消息的通道
+    pub fn register(&self, session_id: i32) -> (u64, UnboundedReceiver<PushMessage>) {
+        let connection_id = self.next_connection_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = unbounded_channel();
+
+        self.lock()
+            .entry(session_id)
+            .or_default()
+            .push((connection_id, sender));
+
+        (connection_id, receiver)
+    }
+
+    /// 注销推送连接
+    pub fn unregister(&self, session_id: i32, connection_id: u64) {
+        let mut connections = self.lock();
+        if let Some(senders) = connections.get_mut(&session_id) {
+            senders.retain(|(id, _)| *id != connection_id);
+            if senders.is_empty() {
+                connections.remove(&session_id);
+            }
+        }
+    }
+
+    /// 向会话的所有推送连接发送指令，返回是否至少送达一个连接
+    pub fn push_command(&self, command: &SessionCommand) -> bool {
+        let connections = self.lock();
+        connections.get(&command.session_id)
+            .map(|senders| senders.iter().fold(false, |delivered, (_, sender)| {
+                sender.send(PushMessage::Command(command.clone())).is_ok() || delivered
+            }))
+            .unwrap_or(false)
+    }
+
+    /// 通知会话的所有推送连接会话已结束，连接收到后关闭
+    pub fn end_session(&self, session_id: i32, reason: &str) {
+        let connections = self.lock();
+        if let Some(senders) = connections.get(&session_id) {
+            for (_, sender) in senders {
+                let _ = sender.send(PushMessage::SessionEnded(reason.to_string()));
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Connections> {
+        self.connections.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -0,0 +1,186 @@
+use diesel::prelude::*;
+use chrono::{Utc, Duration};
+use crate::database::{models::*, Pool};
+use crate::schema::*;
+use crate::services::push::PUSH_HUB;
+use crate::errors::AppError;
+
+type Result<T> = std::result::Result<T, AppError>;
+
+/// 会话指令：强制下线
+pub const COMMAND_FORCE_LOGOUT: &str = "force_logout";
+/// 会话指令：显示消息
+pub const COMMAND_SHOW_MESSAGE: &str = "show_message";
+/// 会话指令：有可用更新
+pub const COMMAND_UPDATE_AVAILABLE: &str = "update_available";
+/// 会话指令：权益变更
+pub const COMMAND_ENTITLEMENT_CHANGED: &str = "entitlement_changed";
+
+const COMMAND_TYPES: [&str; 4] = [
+    COMMAND_FORCE_LOGOUT,
+    COMMAND_SHOW_MESSAGE,
+    COMMAND_UPDATE_AVAILABLE,
+    COMMAND_ENTITLEMENT_CHANGED,
+];
+
+/// 允许向所有在线会话广播的指令类型
+const BROADCAST_COMMAND_TYPES: [&str; 2] = [
+    COMMAND_SHOW_MESSAGE,
+    COMMAND_UPDATE_AVAILABLE,
+];
+
+/// 每批写入的指令数，避免广播时单条语句的参数超过PostgreSQL上限
+const INSERT_BATCH_SIZE: usize = 1000;
+
+/// 写入会话指令，返回生成的指令
+///
+/// 未指定会话时，按该用户当前的每个在线会话分别生成一条指令；之后登录的会话不会收到。
+pub async fn enqueue_session_commands(pool: &Pool, req: EnqueueSessionCommandRequest) -> Result<Vec<SessionCommand>> {
+    if !COMMAND_TYPES.contains(&req.command_type.as_str()) {
+        return Err(AppError::BadRequest(format!("Unknown command type: {}", req.command_type)));
+    }
+
+    let mut conn = pool.get()?;
+
+    let mut query = online_users::table
+        .filter(online_users::user_id.eq(req.user_id))
+        .select((online_users::id, online_users::user_id))
+        .into_boxed();
+    if let Some(session_id) = req.session_id {
+        query = query.filter(online_users::id.eq(session_id));
+    }
+    let sessions = query.load::<(i32, i32)>(&mut conn)?;
+
+    if sessions.is_empty() {
+        return Err(AppError::NotFound("No online session found for this user".to_string()));
+    }
+
+    let payload = req.payload.as_ref().map(|payload| payload.to_string());
+    insert_and_push_commands(&mut conn, &sessions, &req.command_type, req.message.as_deref(), payload.as_deref(), req.ttl_seconds)
+}
+
+/// 向当前所有在线会话广播指令（公告、更新提醒），返回写入的指令数
+///
+/// 之后登录的会话不会收到。
+pub async fn broadcast_session_command(pool: &Pool, req: BroadcastSessionCommandRequest) -> Result<usize> {
+    if !BROADCAST_COMMAND_TYPES.contains(&req.command_type.as_str()) {
+        return Err(AppError::BadRequest(format!("Command type cannot be broadcast: {}", req.command_type)));
+    }
+
+    let mut conn = pool.get()?;
+
+    let sessions = online_users::table
+        .select((online_users::id, online_users::user_id))
+        .load::<(i32, i32)>(&mut conn)?;
+
+    let payload = req.payload.as_ref().map(|payload| payload.to_string());
+    let commands = insert_and_push_commands(&mut conn, &sessions, &req.command_type, req.message.as_deref(), payload.as_deref(), req.ttl_seconds)?;
+
+    Ok(commands.len())
+}
+
+/// 为每个会话（会话ID, 用户ID）写入一条指令，并通过WebSocket即时推送给在线的客户端
+///
+/// 未建立推送连接的会话通过心跳领取。
+fn insert_and_push_commands(
+    conn: &mut PgConnection,
+    sessions: &[(i32, i32)],
+    command_type: &str,
+    message: Option<&str>,
+    payload: Option<&str>,
+    ttl_seconds: Option<i64>,
+) -> Result<Vec<SessionCommand>> {
+    let expires_at = ttl_seconds.map(|ttl| Utc::now() + Duration::seconds(ttl));
+    let mut commands = Vec::with_capacity(sessions.len());
+
+    for batch in sessions.chunks(INSERT_BATCH_SIZE) {
+        let rows: Vec<_> = batch.iter()
+            .map(|(session_id, user_id)| (
+                session_commands::session_id.eq(*session_id),
+                session_commands::user_id.eq(*user_id),
+                session_commands::command_type.eq(command_type),
+                session_commands::message.eq(message),
+                session_commands::payload.eq(payload),
+                session_commands::expires_at.eq(expires_at),
+                session_commands::created_at.eq(Utc::now()),
+            ))
+            .collect();
+
+        let inserted = diesel::insert_into(session_commands::table)
+            .values(&rows)
+            .get_results::<SessionCommand>(conn)?;
+
+        let pushed_ids: Vec<i32> = inserted.iter()
+            .filter(|command| PUSH_HUB.push_command(command))
+            .map(|command| command.id)
+            .collect();
+        if !pushed_ids.is_empty() {
+            diesel::update(session_commands::table)
+                .filter(session_commands::id.eq_any(&pushed_ids))
+                .set(session_commands::delivered_at.eq(Utc::now()))
+                .execute(conn)?;
+        }
+
+        commands.extend(inserted);
+    }
+
+    Ok(commands)
+}
+
+/// 向用户当前所有在线会话下发权益变更指令，用户没有在线会话时忽略
+pub async fn notify_entitlement_changed(pool: &Pool, user_id: i32) -> Result<()> {
+    let req = EnqueueSessionCommandRequest {
+        user_id,
+        session_id: None,
+        command_type: COMMAND_ENTITLEMENT_CHANGED.to_string(),
+        message: None,
+        payload: None,
+        ttl_seconds: None,
+    };
+
+    match enqueue_session_commands(pool, req).await {
+        Ok(_) | Err(AppError::NotFound(_)) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// 确认会话已处理的指令，只会确认属于该会话的指令
+pub fn acknowledge_session_commands(conn: &mut PgConnection, session_id: i32, command_ids: &[i32]) -> Result<()> {
+    if command_ids.is_empty() {
+        return Ok(());
+    }
+
+    diesel::update(session_commands::table)
+        .filter(session_commands::session_id.eq(session_id))
+        .filter(session_commands::id.eq_any(command_ids))
+        .filter(session_commands::acknowledged_at.is_null())
+        .set(session_commands::acknowledged_at.eq(Utc::now()))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// 获取会话未确认且未过期的指令，并记录首次下发时间
+///
+/// 客户端确认之前，每次心跳都会重新下发同一指令。
+pub fn take_pending_session_commands(conn: &mut PgConnection, session_id: i32) -> Result<Vec<SessionCommand>> {
+    let commands = session_commands::table
+        .filter(session_commands::session_id.eq(session_id))
+        .filter(session_commands::acknowledged_at.is_null())
+        .filter(session_commands::expires_at.is_null().or(session_commands::expires_at.gt(Utc::now())))
+        .order_by(session_commands::id.asc())
+        .load::<SessionCommand>(conn)?;
+
+    let undelivered_ids: Vec<i32> = commands.iter()
+        .filter(|command| command.delivered_at.is_none())
+        .map(|command| command.id)
+        .collect();
+    if !undelivered_ids.is_empty() {
+        diesel::update(session_commands::table)
+            .filter(session_commands::id.eq_any(&undelivered_ids))
+            .set(session_commands::delivered_at.eq(Utc::now()))
+            .execute(conn)?;
+    }
+
+    Ok(commands)
+}
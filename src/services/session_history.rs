@@ -0,0 +1,100 @@
+use diesel::prelude::*;
+use chrono::Utc;
+use crate::database::{models::*, Pool};
+use crate::schema::*;
+use crate::services::heartbeat_buffer::HEARTBEAT_BUFFER;
+use crate::services::push::PUSH_HUB;
+use crate::errors::AppError;
+
+type Result<T> = std::result::Result<T, AppError>;
+
+/// 会话结束原因：用户主动登出
+pub const SESSION_END_LOGOUT: &str = "logout";
+/// 会话结束原因：被新登录、管理员指令、改密或解绑设备等踢下线
+pub const SESSION_END_KICKED: &str = "kicked";
+/// 会话结束原因：超时未上报心跳
+pub const SESSION_END_TIMEOUT: &str = "timeout";
+/// 会话结束原因：命中黑名单
+pub const SESSION_END_BANNED: &str = "banned";
+
+/// 结束指定的在线会话，并为每个会话写入一条会话历史，返回实际结束的会话
+///
+/// 所有删除 `online_users` 记录的地方都应通过此函数，其刷新令牌和指令随会话级联删除。
+pub fn end_sessions(conn: &mut PgConnection, session_ids: &[i32], reason: &str) -> Result<Vec<OnlineUser>> {
+    if session_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    conn.transaction(|conn| {
+        let sessions = diesel::delete(online_users::table)
+            .filter(online_users::id.eq_any(session_ids))
+            .get_results::<OnlineUser>(conn)?;
+
+        archive_sessions(conn, &sessions, reason)?;
+
+        Ok(sessions)
+    })
+}
+
+/// 每批写入的会话历史数，避免大量会话同时超时时单条语句的参数超过PostgreSQL上限
+const INSERT_BATCH_SIZE: usize = 1000;
+
+/// 为已删除的在线会话写入会话历史
+///
+/// 超时结束的会话以最后活动时间作为结束时间，其余以当前时间作为结束时间。
+pub fn archive_sessions(conn: &mut PgConnection, sessions: &[OnlineUser], reason: &str) -> Result<()> {
+    let now = Utc::now();
+    let rows: Vec<_> = sessions.iter()
+        .map(|session| {
+            // 合并缓冲中尚未写回的心跳
+            let mut session = session.clone();
+            HEARTBEAT_BUFFER.apply_to(&mut session);
+            let ended_at = if reason == SESSION_END_TIMEOUT { session.last_activity_at } else { now };
+            (
+                session_history::session_id.eq(session.id),
+                session_history::user_id.eq(session.user_id),
+                session_history::hardware_code.eq(session.hardware_code),
+                session_history::software_version.eq(session.software_version),
+                session_history::ip_address.eq(session.ip_address),
+                session_history::started_at.eq(session.login_time),
+                session_history::last_activity_at.eq(session.last_activity_at),
+                session_history::ended_at.eq(ended_at),
+                session_history::end_reason.eq(reason),
+                session_history::duration_seconds.eq((ended_at - session.login_time).num_seconds().max(0)),
+                session_history::created_at.eq(now),
+            )
+        })
+        .collect();
+
+    for batch in rows.chunks(INSERT_BATCH_SIZE) {
+        diesel::insert_into(session_history::table)
+            .values(batch)
+            .execute(conn)?;
+    }
+
+    // 通知会话的WebSocket推送连接关闭
+    for session in sessions {
+        PUSH_HUB.end_session(session.id, reason);
+    }
+
+    Ok(())
+}
+
+/// 分页获取用户的会话历史，按开始时间从新到旧排列，返回总条数和当前页记录
+pub async fn get_session_history(pool: &Pool, user_id: i32, page: i64, page_size: i64) -> Result<(i64, Vec<SessionHistory>)> {
+    let mut conn = pool.get()?;
+
+    let total = session_history::table
+        .filter(session_history::user_id.eq(user_id))
+        .count()
+        .get_result::<i64>(&mut conn)?;
+
+    let history = session_history::table
+        .filter(session_history::user_id.eq(user_id))
+        .order_by((session_history::started_at.desc(), session_history::id.desc()))
+        .limit(page_size)
+        .offset((page - 1) * page_size)
+        .load::<SessionHistory>(&mut conn)?;
+
+    Ok((total, history))
+}
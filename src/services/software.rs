@@ -41,3 +41,50 @@ pub async fn check_software_access(pool: &Pool, user_id: i32, software_id: i32)
     // 检查是否有权限使用
     Ok(current_vip_level >= software.required_vip_level)
 }
+
+// 获取软件的客户端密钥，未设置时返回None
+pub async fn get_client_secret(pool: &Pool, software_id: i32) -> Result<Option<String>> {
+    let mut conn = pool.get()?;
+    
+    let client_secret = software_client_secrets::table
+        .find(software_id)
+        .select(software_client_secrets::client_secret)
+        .first::<String>(&mut conn)
+        .optional()?;
+    
+    Ok(client_secret)
+}
+
+// 为软件生成新的客户端密钥（替换旧密钥），软件不存在时返回None
+pub async fn rotate_client_secret(pool: &Pool, software_id: i32) -> Result<Option<String>> {
+    let mut conn = pool.get()?;
+    
+    let software_exists = software::table
+        .find(software_id)
+        .select(software::id)
+        .first::<i32>(&mut conn)
+        .optional()?
+        .is_some();
+    if !software_exists {
+        return Ok(None);
+    }
+    
+    let client_secret = crate::utils::crypto::generate_random_token(32);
+    
+    diesel::insert_into(software_client_secrets::table)
+        .values((
+            software_client_secrets::software_id.eq(software_id),
+            software_client_secrets::client_secret.eq(&client_secret),
+            software_client_secrets::created_at.eq(Utc::now()),
+            software_client_secrets::updated_at.eq(Utc::now()),
+        ))
+        .on_conflict(software_client_secrets::software_id)
+        .do_update()
+        .set((
+            software_client_secrets::client_secret.eq(&client_secret),
+            software_client_secrets::updated_at.eq(Utc::now()),
+        ))
+        .execute(&mut conn)?;
+    
+    Ok(Some(client_secret))
+}
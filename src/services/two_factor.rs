@@ -0,0 +1,228 @@
+use diesel::prelude::*;
+use chrono::{DateTime, Utc};
+use crate::database::{models::*, Pool};
+use crate::schema::*;
+use crate::config::Config;
+use crate::errors::AppError;
+use crate::utils::crypto::{generate_random_token, sha256_hex, verify_password};
+use crate::utils::totp;
+
+type Result<T> = std::result::Result<T, AppError>;
+
+/// 每次启用两步验证时生成的备用码数量
+const BACKUP_CODE_COUNT: usize = 10;
+
+/// 开始绑定两步验证，返回Base32密钥和 otpauth:// URI
+///
+/// 重复调用会生成新的密钥，直到用户用首个验证码确认为止
+pub async fn enroll_two_factor(pool: &Pool, user_id: i32, config: &Config) -> Result<(String, String)> {
+    let mut conn = pool.get()?;
+
+    let user = users::table
+        .find(user_id)
+        .first::<User>(&mut conn)?;
+
+    let existing = user_totp::table
+        .filter(user_totp::user_id.eq(user_id))
+        .first::<UserTotp>(&mut conn)
+        .optional()?;
+
+    if existing.as_ref().is_some_and(|totp| totp.enabled) {
+        return Err(AppError::BadRequest("Two-factor authentication is already enabled".to_string()));
+    }
+
+    let secret = totp::generate_secret();
+
+    match existing {
+        Some(totp) => {
+            diesel::update(user_totp::table.find(totp.id))
+                .set((
+                    user_totp::secret.eq(&secret),
+                    user_totp::last_used_step.eq(None::<i64>),
+                    user_totp::updated_at.eq(Utc::now()),
+                ))
+                .execute(&mut conn)?;
+        },
+        None => {
+            diesel::insert_into(user_totp::table)
+                .values((
+                    user_totp::user_id.eq(user_id),
+                    user_totp::secret.eq(&secret),
+                    user_totp::enabled.eq(false),
+                    user_totp::created_at.eq(Utc::now()),
+                    user_totp::updated_at.eq(Utc::now()),
+                ))
+                .execute(&mut conn)?;
+        }
+    }
+
+    let otpauth_uri = totp::build_otpauth_uri(&secret, &config.totp_issuer, &user.username);
+
+    Ok((secret, otpauth_uri))
+}
+
+/// 用首个验证码确认绑定并启用两步验证，返回一次性备用码
+pub async fn confirm_two_factor(pool: &Pool, user_id: i32, code: &str) -> Result<Vec<String>> {
+    let mut conn = pool.get()?;
+
+    let totp = user_totp::table
+        .filter(user_totp::user_id.eq(user_id))
+        .first::<UserTotp>(&mut conn)
+        .optional()?
+        .ok_or_else(|| AppError::BadRequest("Two-factor authentication has not been enrolled".to_string()))?;
+
+    if totp.enabled {
+        return Err(AppError::BadRequest("Two-factor authentication is already enabled".to_string()));
+    }
+
+    let step = totp::verify_code(&totp.secret, code, Utc::now().timestamp())
+        .ok_or_else(|| AppError::BadRequest("Invalid two-factor code".to_string()))?;
+
+    diesel::update(user_totp::table.find(totp.id))
+        .set((
+            user_totp::enabled.eq(true),
+            user_totp::last_used_step.eq(step),
+            user_totp::confirmed_at.eq(Utc::now()),
+            user_totp::updated_at.eq(Utc::now()),
+        ))
+        .execute(&mut conn)?;
+
+    // 生成新的备用码，旧备用码全部作废
+    diesel::delete(totp_backup_codes::table)
+        .filter(totp_backup_codes::user_id.eq(user_id))
+        .execute(&mut conn)?;
+
+    let backup_codes: Vec<String> = (0..BACKUP_CODE_COUNT)
+        .map(|_| generate_random_token(5))
+        .collect();
+
+    for backup_code in &backup_codes {
+        diesel::insert_into(totp_backup_codes::table)
+            .values((
+                totp_backup_codes::user_id.eq(user_id),
+                totp_backup_codes::code_hash.eq(sha256_hex(backup_code)),
+                totp_backup_codes::created_at.eq(Utc::now()),
+            ))
+            .execute(&mut conn)?;
+    }
+
+    Ok(backup_codes)
+}
+
+/// 关闭两步验证，需要同时提供当前密码和验证码（或备用码）
+pub async fn disable_two_factor(pool: &Pool, user_id: i32, password: &str, code: &str) -> Result<()> {
+    let mut conn = pool.get()?;
+
+    let user = users::table
+        .find(user_id)
+        .first::<User>(&mut conn)?;
+
+    if !verify_password(password, &user.password_hash)? {
+        return Err(AppError::Unauthorized("Invalid password".to_string()));
+    }
+
+    if !is_two_factor_enabled(&mut conn, user_id)? {
+        return Err(AppError::BadRequest("Two-factor authentication is not enabled".to_string()));
+    }
+
+    if !verify_second_factor(&mut conn, user_id, code)? {
+        return Err(AppError::BadRequest("Invalid two-factor code".to_string()));
+    }
+
+    diesel::delete(totp_backup_codes::table)
+        .filter(totp_backup_codes::user_id.eq(user_id))
+        .execute(&mut conn)?;
+
+    diesel::delete(user_totp::table)
+        .filter(user_totp::user_id.eq(user_id))
+        .execute(&mut conn)?;
+
+    Ok(())
+}
+
+/// 用户是否已启用两步验证
+pub fn is_two_factor_enabled(conn: &mut PgConnection, user_id: i32) -> Result<bool> {
+    let enabled = user_totp::table
+        .filter(user_totp::user_id.eq(user_id))
+        .filter(user_totp::enabled.eq(true))
+        .first::<UserTotp>(conn)
+        .optional()?
+        .is_some();
+
+    Ok(enabled)
+}
+
+/// 校验TOTP验证码或备用码
+///
+/// TOTP验证码在同一时间步内只能使用一次，备用码使用后立即失效
+pub fn verify_second_factor(conn: &mut PgConnection, user_id: i32, code: &str) -> Result<bool> {
+    let totp = match user_totp::table
+        .filter(user_totp::user_id.eq(user_id))
+        .filter(user_totp::enabled.eq(true))
+        .first::<UserTotp>(conn)
+        .optional()? {
+        Some(totp) => totp,
+        None => return Ok(false),
+    };
+
+    if let Some(step) = totp::verify_code(&totp.secret, code, Utc::now().timestamp()) {
+        // 只接受比上次使用更晚的时间步，防止验证码被重放
+        let updated_rows = diesel::update(user_totp::table.find(totp.id))
+            .filter(user_totp::last_used_step.is_null().or(user_totp::last_used_step.lt(step)))
+            .set((
+                user_totp::last_used_step.eq(step),
+                user_totp::updated_at.eq(Utc::now()),
+            ))
+            .execute(conn)?;
+
+        return Ok(updated_rows > 0);
+    }
+
+    // 尝试作为备用码校验
+    let normalized: String = code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+
+    let used_rows = diesel::update(totp_backup_codes::table)
+        .filter(totp_backup_codes::user_id.eq(user_id))
+        .filter(totp_backup_codes::code_hash.eq(sha256_hex(&normalized)))
+        .filter(totp_backup_codes::used_at.is_null())
+        .set(totp_backup_codes::used_at.eq(Utc::now()))
+        .execute(conn)?;
+
+    Ok(used_rows > 0)
+}
+
+/// 将两步验证待完成令牌标记为已使用，令牌已被使用过时返回 Unauthorized
+///
+/// 按jti记录，保证同一令牌只能完成一次登录；顺带删除已过期的记录。
+pub fn consume_two_factor_token(conn: &mut PgConnection, claims: &Claims) -> Result<()> {
+    if claims.jti.is_empty() {
+        return Err(AppError::Unauthorized("Invalid two-factor token".to_string()));
+    }
+
+    diesel::delete(consumed_two_factor_tokens::table)
+        .filter(consumed_two_factor_tokens::expires_at.lt(Utc::now()))
+        .execute(conn)?;
+
+    let user_id = claims.sub.parse::<i32>()
+        .map_err(|_| AppError::Unauthorized("Invalid two-factor token".to_string()))?;
+    let expires_at = DateTime::<Utc>::from_timestamp(claims.exp, 0).unwrap_or_else(Utc::now);
+
+    let inserted = diesel::insert_into(consumed_two_factor_tokens::table)
+        .values((
+            consumed_two_factor_tokens::jti.eq(&claims.jti),
+            consumed_two_factor_tokens::user_id.eq(user_id),
+            consumed_two_factor_tokens::expires_at.eq(expires_at),
+            consumed_two_factor_tokens::created_at.eq(Utc::now()),
+        ))
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    if inserted == 0 {
+        return Err(AppError::Unauthorized("Two-factor token has already been used".to_string()));
+    }
+
+    Ok(())
+}
@@ -3,6 +3,7 @@ use anyhow::Result;
 use chrono::Utc;
 use crate::database::{models::*, Pool};
 use crate::schema::*;
+use crate::services::heartbeat_buffer::HEARTBEAT_BUFFER;
 
 pub async fn get_user_info(pool: &Pool, user_id: i32) -> Result<User> {
     let mut conn = pool.get()?;
@@ -102,3 +103,39 @@ pub async fn update_user_vip(pool: &Pool, user_id: i32, new_vip_level: i32, dura
     
     Ok(updated_user)
 }
+
+// 获取用户当前的在线会话，按最近活动时间从新到旧排列
+pub async fn get_user_sessions(pool: &Pool, user_id: i32) -> Result<Vec<OnlineUser>> {
+    let mut conn = pool.get()?;
+    
+    let mut sessions = online_users::table
+        .filter(online_users::user_id.eq(user_id))
+        .load::<OnlineUser>(&mut conn)?;
+    
+    // 合并缓冲中尚未写回的心跳后再排序
+    for session in &mut sessions {
+        HEARTBEAT_BUFFER.apply_to(session);
+    }
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.last_activity_at));
+    
+    Ok(sessions)
+}
+
+// 分页获取用户的登录历史（包括失败的登录尝试），返回总条数和当前页记录
+pub async fn get_login_history(pool: &Pool, user_id: i32, page: i64, page_size: i64) -> Result<(i64, Vec<LoginLog>)> {
+    let mut conn = pool.get()?;
+    
+    let total = login_logs::table
+        .filter(login_logs::user_id.eq(user_id))
+        .count()
+        .get_result::<i64>(&mut conn)?;
+    
+    let logs = login_logs::table
+        .filter(login_logs::user_id.eq(user_id))
+        .order_by((login_logs::login_time.desc(), login_logs::id.desc()))
+        .limit(page_size)
+        .offset((page - 1) * page_size)
+        .load::<LoginLog>(&mut conn)?;
+    
+    Ok((total, logs))
+}
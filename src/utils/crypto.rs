@@ -1,11 +1,25 @@
-use bcrypt::{hash, verify, DEFAULT_COST};
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use argon2::password_hash::{rand_core::OsRng, SaltString};
+use log::warn;
+use rand::RngCore;
 use regex::Regex;
+use hmac::{Hmac, KeyInit, Mac};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
 use crate::errors::AppError;
 
 type Result<T> = std::result::Result<T, AppError>;
 
+/// 内置的常见弱密码列表
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
 /// 检查密码复杂度
-pub fn check_password_strength(password: &str, config: &crate::config::Config) -> Result<()> {
+///
+/// `user_inputs` 为用户名、邮箱等个人信息，密码中不能包含这些内容，同时参与强度评分
+pub fn check_password_strength(password: &str, user_inputs: &[&str], config: &crate::config::Config) -> Result<()> {
     // 密码长度检查
     if password.len() < config.password_min_length {
         return Err(AppError::BadRequest(format!("Password must be at least {} characters long", config.password_min_length)));
@@ -43,17 +57,157 @@ pub fn check_password_strength(password: &str, config: &crate::config::Config) -
         }
     }
     
+    // 不能包含用户名或邮箱前缀
+    let lowercase_password = password.to_lowercase();
+    let contains_user_input = user_inputs.iter()
+        .map(|input| input.split('@').next().unwrap_or_default().to_lowercase())
+        .any(|input| input.chars().count() >= 3 && lowercase_password.contains(&input));
+    if contains_user_input {
+        return Err(AppError::BadRequest("Password must not contain your username or email".to_string()));
+    }
+    
+    // 不能是常见弱密码
+    if is_common_password(password) {
+        return Err(AppError::BadRequest("Password is too common".to_string()));
+    }
+    
+    // 不能出现在已泄露密码库中
+    if !config.password_breach_dir.is_empty() && is_breached_password(password, &config.password_breach_dir) {
+        return Err(AppError::BadRequest("Password has appeared in a data breach".to_string()));
+    }
+    
+    // 强度评分（0-4）不能低于配置的最低分
+    let score = u8::from(zxcvbn::zxcvbn(password, user_inputs).score());
+    if score < config.password_min_score {
+        return Err(AppError::BadRequest("Password is too weak, please choose a stronger password".to_string()));
+    }
+    
     Ok(())
 }
 
+/// 是否为常见弱密码，忽略大小写以及首尾附加的数字和符号（如 Password1!）
+fn is_common_password(password: &str) -> bool {
+    let lowercase_password = password.to_lowercase();
+    let stripped_password = lowercase_password.trim_matches(|c: char| !c.is_alphabetic());
+
+    COMMON_PASSWORDS.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .any(|common| common == lowercase_password || (stripped_password.chars().count() >= 4 && common == stripped_password))
+}
+
+/// 在本地已泄露密码库中查找密码
+///
+/// 密码库按SHA-1前5位十六进制分文件存放（`{dir}/{PREFIX}.txt`），每行格式为 `后35位:出现次数`，
+/// 与 Have I Been Pwned 的范围查询格式一致。每次只读取对应前缀的文件。
+fn is_breached_password(password: &str, breach_dir: &str) -> bool {
+    let digest = to_hex(&Sha1::digest(password.as_bytes())).to_uppercase();
+    let (prefix, suffix) = digest.split_at(5);
+
+    let content = match fs::read_to_string(Path::new(breach_dir).join(format!("{}.txt", prefix))) {
+        Ok(content) => content,
+        Err(err) if err.kind() == ErrorKind::NotFound => return false,
+        Err(err) => {
+            warn!("Failed to read breached password file for prefix {}: {}", prefix, err);
+            return false;
+        }
+    };
+
+    content.lines()
+        .filter_map(|line| line.split(':').next())
+        .any(|line_suffix| line_suffix.trim().eq_ignore_ascii_case(suffix))
+}
+
 /// 加密密码
-pub fn hash_password(password: &str, config: &crate::config::Config) -> Result<String> {
+pub fn hash_password(password: &str, user_inputs: &[&str], config: &crate::config::Config) -> Result<String> {
     // 先检查密码强度
-    check_password_strength(password, config)?;
-    Ok(hash(password, DEFAULT_COST)?) 
+    check_password_strength(password, user_inputs, config)?;
+    compute_password_hash(password, config)
+}
+
+/// 使用当前配置的Argon2id参数计算密码哈希，不做强度检查
+///
+/// 用于登录时升级旧哈希，已有密码即使不满足新的强度规则也不应被拒绝
+pub fn compute_password_hash(password: &str, config: &crate::config::Config) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let password_hash = argon2_hasher(config)?
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|err| AppError::PasswordError(err.to_string()))?;
+    Ok(password_hash.to_string())
 }
 
-/// 验证密码
+/// 验证密码，根据哈希前缀识别Argon2或旧的bcrypt格式
 pub fn verify_password(password: &str, hash: &str) -> Result<bool> {
-    Ok(verify(password, hash)?) 
+    if hash.starts_with("$argon2") {
+        let parsed_hash = PasswordHash::new(hash)
+            .map_err(|err| AppError::PasswordError(err.to_string()))?;
+        return Ok(Argon2::default().verify_password(password.as_bytes(), &parsed_hash).is_ok());
+    }
+
+    Ok(bcrypt::verify(password, hash)?)
+}
+
+/// 判断密码哈希是否需要升级：旧的bcrypt哈希，或参数与当前配置不一致的Argon2哈希
+pub fn password_needs_rehash(hash: &str, config: &crate::config::Config) -> bool {
+    let parsed_hash = match PasswordHash::new(hash) {
+        Ok(parsed_hash) if parsed_hash.algorithm == Algorithm::Argon2id.ident() => parsed_hash,
+        _ => return true,
+    };
+
+    match Params::try_from(&parsed_hash) {
+        Ok(params) => {
+            parsed_hash.version != Some(Version::V0x13.into())
+                || params.m_cost() != config.argon2_memory_kib
+                || params.t_cost() != config.argon2_iterations
+                || params.p_cost() != config.argon2_parallelism
+        }
+        Err(_) => true,
+    }
+}
+
+/// 按配置创建Argon2id哈希器
+fn argon2_hasher(config: &crate::config::Config) -> Result<Argon2<'static>> {
+    let params = Params::new(config.argon2_memory_kib, config.argon2_iterations, config.argon2_parallelism, None)
+        .map_err(|err| AppError::InternalServerError(format!("Invalid Argon2 parameters: {}", err)))?;
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+/// 计算字符串的SHA-256摘要，返回小写十六进制字符串
+pub fn sha256_hex(input: &str) -> String {
+    sha256_hex_bytes(input.as_bytes())
+}
+
+/// 计算字节序列的SHA-256摘要，返回小写十六进制字符串
+pub fn sha256_hex_bytes(input: &[u8]) -> String {
+    to_hex(&Sha256::digest(input))
+}
+
+/// 计算HMAC-SHA256，返回小写十六进制字符串
+///
+/// 用于存储位数较少的验证码（仅用SHA-256的话可以被穷举还原），以及校验客户端请求签名
+pub fn hmac_sha256_hex(key: &str, input: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(input.as_bytes());
+    to_hex(&mac.finalize().into_bytes())
+}
+
+/// 生成指定字节数的随机令牌，返回小写十六进制字符串
+pub fn generate_random_token(num_bytes: usize) -> String {
+    let mut bytes = vec![0u8; num_bytes];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    to_hex(&bytes)
+}
+
+/// 以恒定时间比较两个字符串，避免通过响应时间推测密钥内容
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// 将字节序列编码为小写十六进制字符串
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
 }
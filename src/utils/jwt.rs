@@ -1,12 +1,74 @@
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
-use serde::{Deserialize, Serialize};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{de::DeserializeOwned, Serialize};
 use chrono::{Utc, Duration};
 use crate::database::models::Claims;
 use crate::config::Config;
 use crate::errors::AppError;
+use crate::utils::crypto::generate_random_token;
 
 type Result<T> = std::result::Result<T, AppError>;
 
+/// 刷新令牌有效期（天）
+pub const REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+
+/// 生成令牌唯一标识
+fn generate_jti() -> String {
+    generate_random_token(16)
+}
+
+/// 签名任意载荷
+///
+/// 配置了Ed25519签名密钥时使用EdDSA签名并在头部写入kid，否则回退为使用 JWT_SECRET 的HS256签名
+pub fn sign_claims<T: Serialize>(claims: &T, config: &Config) -> Result<String> {
+    let token = match &config.jwt_keys.signing_key {
+        Some(signing_key) => {
+            let mut header = Header::new(Algorithm::EdDSA);
+            header.kid = Some(signing_key.kid.clone());
+            encode(&header, claims, &signing_key.encoding_key)?
+        },
+        None => encode(&Header::default(), claims, &EncodingKey::from_secret(config.jwt_secret.as_bytes()))?,
+    };
+    Ok(token)
+}
+
+/// 验证签名和有效期并解析载荷
+///
+/// 带kid的令牌使用对应的Ed25519公钥验证；不带kid的HS256令牌仅在未配置签名密钥或允许旧令牌时接受
+pub fn decode_claims<T: DeserializeOwned>(token: &str, config: &Config) -> Result<T> {
+    let header = decode_header(token)?;
+    
+    let decoded = match header.kid {
+        Some(kid) => {
+            let decoding_key = config.jwt_keys.decoding_keys.get(&kid)
+                .ok_or_else(|| AppError::JwtError(format!("Unknown signing key: {}", kid)))?;
+            let mut validation = Validation::new(Algorithm::EdDSA);
+            validation.validate_exp = true;
+            decode::<T>(token, decoding_key, &validation)?
+        },
+        None => {
+            if config.jwt_keys.signing_key.is_some() && !config.jwt_accept_legacy_hs256 {
+                return Err(AppError::JwtError("Token is missing a key id".to_string()));
+            }
+            let mut validation = Validation::new(Algorithm::HS256);
+            validation.validate_exp = true;
+            decode::<T>(token, &DecodingKey::from_secret(config.jwt_secret.as_bytes()), &validation)?
+        },
+    };
+    
+    Ok(decoded.claims)
+}
+
+/// 验证令牌并检查令牌类型
+fn verify_typed_token(token: &str, token_type: &str, config: &Config) -> Result<Claims> {
+    let claims = decode_claims::<Claims>(token, config)?;
+    
+    if claims.token_type != token_type {
+        return Err(AppError::Unauthorized("Invalid token type".to_string()));
+    }
+    
+    Ok(claims)
+}
+
 /// 生成访问令牌
 pub fn generate_access_token(user_id: i32, username: &str, config: &Config) -> Result<String> {
     let expiration = Utc::now() + Duration::hours(1); // 访问令牌有效期1小时
@@ -15,52 +77,34 @@ pub fn generate_access_token(user_id: i32, username: &str, config: &Config) -> R
         username: username.to_string(),
         exp: expiration.timestamp(),
         token_type: "access".to_string(),
+        jti: generate_jti(),
     };
     
-    let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(config.jwt_secret.as_bytes()))?;
-    Ok(token)
+    sign_claims(&claims, config)
 }
 
 /// 生成刷新令牌
 pub fn generate_refresh_token(user_id: i32, username: &str, config: &Config) -> Result<String> {
-    let expiration = Utc::now() + Duration::days(30); // 刷新令牌有效期30天
+    let expiration = Utc::now() + Duration::days(REFRESH_TOKEN_TTL_DAYS);
     let claims = Claims {
         sub: user_id.to_string(),
         username: username.to_string(),
         exp: expiration.timestamp(),
         token_type: "refresh".to_string(),
+        jti: generate_jti(),
     };
     
-    let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(config.jwt_secret.as_bytes()))?;
-    Ok(token)
+    sign_claims(&claims, config)
 }
 
 /// 验证访问令牌
 pub fn verify_access_token(token: &str, config: &Config) -> Result<Claims> {
-    let mut validation = Validation::default();
-    validation.validate_exp = true;
-    
-    let decoded = decode::<Claims>(token, &DecodingKey::from_secret(config.jwt_secret.as_bytes()), &validation)?;
-    
-    if decoded.claims.token_type != "access" {
-        return Err(AppError::Unauthorized("Invalid token type".to_string()));
-    }
-    
-    Ok(decoded.claims)
+    verify_typed_token(token, "access", config)
 }
 
 /// 验证刷新令牌
 pub fn verify_refresh_token(token: &str, config: &Config) -> Result<Claims> {
-    let mut validation = Validation::default();
-    validation.validate_exp = true;
-    
-    let decoded = decode::<Claims>(token, &DecodingKey::from_secret(config.jwt_secret.as_bytes()), &validation)?;
-    
-    if decoded.claims.token_type != "refresh" {
-        return Err(AppError::Unauthorized("Invalid token type".to_string()));
-    }
-    
-    Ok(decoded.claims)
+    verify_typed_token(token, "refresh", config)
 }
 
 /// 生成JWT令牌（保留旧函数，用于兼容）
@@ -81,24 +125,15 @@ pub fn generate_reset_token(user_id: i32, email: &str, config: &Config) -> Resul
         username: email.to_string(), // 使用邮箱作为username字段
         exp: expiration.timestamp(),
         token_type: "reset".to_string(),
+        jti: generate_jti(),
     };
     
-    let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(config.jwt_secret.as_bytes()))?;
-    Ok(token)
+    sign_claims(&claims, config)
 }
 
 /// 验证密码重置令牌
 pub fn verify_reset_token(token: &str, config: &Config) -> Result<Claims> {
-    let mut validation = Validation::default();
-    validation.validate_exp = true;
-    
-    let decoded = decode::<Claims>(token, &DecodingKey::from_secret(config.jwt_secret.as_bytes()), &validation)?;
-    
-    if decoded.claims.token_type != "reset" {
-        return Err(AppError::Unauthorized("Invalid token type".to_string()));
-    }
-    
-    Ok(decoded.claims)
+    verify_typed_token(token, "reset", config)
 }
 
 /// 生成激活令牌
@@ -109,22 +144,32 @@ pub fn generate_activation_token(user_id: i32, email: &str, config: &Config) ->
         username: email.to_string(), // 使用邮箱作为username字段
         exp: expiration.timestamp(),
         token_type: "activation".to_string(),
+        jti: generate_jti(),
     };
     
-    let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(config.jwt_secret.as_bytes()))?;
-    Ok(token)
+    sign_claims(&claims, config)
 }
 
 /// 验证激活令牌
 pub fn verify_activation_token(token: &str, config: &Config) -> Result<Claims> {
-    let mut validation = Validation::default();
-    validation.validate_exp = true;
-    
-    let decoded = decode::<Claims>(token, &DecodingKey::from_secret(config.jwt_secret.as_bytes()), &validation)?;
-    
-    if decoded.claims.token_type != "activation" {
-        return Err(AppError::Unauthorized("Invalid token type".to_string()));
-    }
+    verify_typed_token(token, "activation", config)
+}
+
+/// 生成两步验证待完成令牌
+pub fn generate_two_factor_token(user_id: i32, username: &str, config: &Config) -> Result<String> {
+    let expiration = Utc::now() + Duration::minutes(5); // 两步验证令牌有效期5分钟
+    let claims = Claims {
+        sub: user_id.to_string(),
+        username: username.to_string(),
+        exp: expiration.timestamp(),
+        token_type: "2fa_pending".to_string(),
+        jti: generate_jti(),
+    };
     
-    Ok(decoded.claims)
+    sign_claims(&claims, config)
+}
+
+/// 验证两步验证待完成令牌
+pub fn verify_two_factor_token(token: &str, config: &Config) -> Result<Claims> {
+    verify_typed_token(token, "2fa_pending", config)
 }
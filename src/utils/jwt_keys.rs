@@ -0,0 +1,136 @@
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use jsonwebtoken::{DecodingKey, EncodingKey};
+use log::info;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Ed25519公钥 SubjectPublicKeyInfo 的DER前缀，其后紧跟32字节原始公钥
+const ED25519_SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+/// 用于签名的当前密钥
+pub struct SigningKey {
+    pub kid: String,
+    pub encoding_key: EncodingKey,
+}
+
+/// JWKS中的单个公钥
+#[derive(Debug, Clone, Serialize)]
+pub struct Jwk {
+    pub kty: String,
+    pub crv: String,
+    pub x: String,
+    pub kid: String,
+    #[serde(rename = "use")]
+    pub key_use: String,
+    pub alg: String,
+}
+
+/// JWKS响应
+#[derive(Debug, Clone, Serialize)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+/// 从磁盘加载的Ed25519密钥集合
+///
+/// 密钥目录中每个 `{kid}.pub.pem` 文件对应一个验证密钥，并在JWKS中公开；
+/// 同目录下存在 `{kid}.key.pem` 私钥的密钥才能用于签名。
+/// 删除私钥文件即可让密钥退役：新令牌改用其他密钥签名，旧令牌在过期前仍可验证。
+pub struct JwtKeySet {
+    pub signing_key: Option<SigningKey>,
+    pub decoding_keys: HashMap<String, DecodingKey>,
+    pub jwks: JwkSet,
+}
+
+impl JwtKeySet {
+    /// 空密钥集合，此时所有令牌回退为使用 JWT_SECRET 的HS256签名
+    pub fn empty() -> Self {
+        Self {
+            signing_key: None,
+            decoding_keys: HashMap::new(),
+            jwks: JwkSet { keys: Vec::new() },
+        }
+    }
+
+    /// 从密钥目录加载密钥
+    ///
+    /// 未指定 `signing_kid` 时，使用按名称排序最大的、带私钥的密钥签名
+    pub fn load(dir: &str, signing_kid: Option<&str>) -> Result<Self, String> {
+        let mut key_set = Self::empty();
+        let mut private_keys: Vec<(String, EncodingKey)> = Vec::new();
+
+        let entries = fs::read_dir(dir).map_err(|err| format!("Failed to read JWT key directory {}: {}", dir, err))?;
+        let mut public_key_files: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| name.ends_with(".pub.pem"))
+            .collect();
+        public_key_files.sort();
+
+        for file_name in public_key_files {
+            let kid = file_name.trim_end_matches(".pub.pem").to_string();
+            let public_pem = fs::read(Path::new(dir).join(&file_name))
+                .map_err(|err| format!("Failed to read public key {}: {}", file_name, err))?;
+
+            let decoding_key = DecodingKey::from_ed_pem(&public_pem)
+                .map_err(|err| format!("Invalid Ed25519 public key {}: {}", file_name, err))?;
+            let raw_public_key = ed25519_raw_public_key(&public_pem)
+                .ok_or_else(|| format!("Invalid Ed25519 public key {}", file_name))?;
+
+            key_set.decoding_keys.insert(kid.clone(), decoding_key);
+            key_set.jwks.keys.push(Jwk {
+                kty: "OKP".to_string(),
+                crv: "Ed25519".to_string(),
+                x: URL_SAFE_NO_PAD.encode(raw_public_key),
+                kid: kid.clone(),
+                key_use: "sig".to_string(),
+                alg: "EdDSA".to_string(),
+            });
+
+            let private_key_path = Path::new(dir).join(format!("{}.key.pem", kid));
+            if private_key_path.exists() {
+                let private_pem = fs::read(&private_key_path)
+                    .map_err(|err| format!("Failed to read private key for {}: {}", kid, err))?;
+                let encoding_key = EncodingKey::from_ed_pem(&private_pem)
+                    .map_err(|err| format!("Invalid Ed25519 private key for {}: {}", kid, err))?;
+                private_keys.push((kid, encoding_key));
+            }
+        }
+
+        key_set.signing_key = match signing_kid {
+            Some(signing_kid) => {
+                let (kid, encoding_key) = private_keys.into_iter()
+                    .find(|(kid, _)| kid == signing_kid)
+                    .ok_or_else(|| format!("Signing key {} not found or has no private key", signing_kid))?;
+                Some(SigningKey { kid, encoding_key })
+            },
+            None => private_keys.pop().map(|(kid, encoding_key)| SigningKey { kid, encoding_key }),
+        };
+
+        match &key_set.signing_key {
+            Some(signing_key) => info!("Loaded {} JWT verification keys, signing with key {}", key_set.decoding_keys.len(), signing_key.kid),
+            None => info!("Loaded {} JWT verification keys, no signing key available", key_set.decoding_keys.len()),
+        }
+
+        Ok(key_set)
+    }
+}
+
+/// 从PEM格式的Ed25519公钥中提取32字节原始公钥
+fn ed25519_raw_public_key(pem: &[u8]) -> Option<Vec<u8>> {
+    let pem = std::str::from_utf8(pem).ok()?;
+    let body: String = pem.lines()
+        .filter(|line| !line.starts_with("-----"))
+        .map(|line| line.trim())
+        .collect();
+    let der = STANDARD.decode(body).ok()?;
+
+    if der.len() != ED25519_SPKI_PREFIX.len() + 32 || !der.starts_with(&ED25519_SPKI_PREFIX) {
+        return None;
+    }
+
+    Some(der[ED25519_SPKI_PREFIX.len()..].to_vec())
+}
@@ -1,4 +1,7 @@
 pub mod crypto;
 pub mod email;
 pub mod jwt;
+pub mod jwt_keys;
 pub mod logger;
+pub mod nonce_store;
+pub mod totp;
@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 短期有效的nonce存储，用于拒绝重放的签名请求
+///
+/// 仅保存在进程内存中，多实例部署时每个实例分别记录。
+pub struct NonceStore {
+    state: Mutex<NonceState>,
+}
+
+struct NonceState {
+    entries: HashMap<String, Instant>,
+    next_purge_at: Instant,
+}
+
+impl Default for NonceStore {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(NonceState {
+                entries: HashMap::new(),
+                next_purge_at: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl NonceStore {
+    /// 记录一个nonce并保留 `ttl` 时长，有效期内已记录过时返回 false
+    pub fn insert(&self, key: &str, ttl: Duration) -> bool {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        // 定期清理已过期的记录，避免内存持续增长
+        if now >= state.next_purge_at {
+            state.entries.retain(|_, expires_at| *expires_at > now);
+            state.next_purge_at = now + ttl;
+        }
+
+        match state.entries.get(key) {
+            Some(expires_at) if *expires_at > now => false,
+            _ => {
+                state.entries.insert(key.to_string(), now + ttl);
+                true
+            }
+        }
+    }
+}
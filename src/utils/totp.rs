@@ -0,0 +1,177 @@
+use hmac::{Hmac, KeyInit, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+
+/// TOTP时间步长（秒）
+pub const TOTP_PERIOD: i64 = 30;
+/// TOTP验证码位数
+pub const TOTP_DIGITS: u32 = 6;
+/// 允许的时钟偏差（前后各多少个时间步）
+const TOTP_SKEW_STEPS: i64 = 1;
+
+const BASE32_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// 生成160位随机密钥，返回Base32编码（不带填充）
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 20];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base32_encode(&bytes)
+}
+
+/// 构建认证器应用使用的 otpauth:// URI
+pub fn build_otpauth_uri(secret: &str, issuer: &str, account_name: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(issuer),
+        percent_encode(account_name),
+        secret,
+        percent_encode(issuer),
+        TOTP_DIGITS,
+        TOTP_PERIOD,
+    )
+}
+
+/// 计算Unix时间戳对应的时间步
+pub fn time_step(unix_seconds: i64) -> i64 {
+    unix_seconds.div_euclid(TOTP_PERIOD)
+}
+
+/// 按 RFC 6238 计算指定时间步的验证码
+pub fn generate_code(secret: &str, step: i64) -> Option<String> {
+    let key = base32_decode(secret)?;
+    let mut mac = Hmac::<Sha1>::new_from_slice(&key).ok()?;
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // RFC 4226 动态截断
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = ((digest[offset] as u32 & 0x7f) << 24)
+        | ((digest[offset + 1] as u32) << 16)
+        | ((digest[offset + 2] as u32) << 8)
+        | (digest[offset + 3] as u32);
+
+    Some(format!("{:0width$}", binary % 10u32.pow(TOTP_DIGITS), width = TOTP_DIGITS as usize))
+}
+
+/// 在允许的时钟偏差内校验验证码，成功时返回匹配的时间步
+pub fn verify_code(secret: &str, code: &str, unix_seconds: i64) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let current_step = time_step(unix_seconds);
+    (-TOTP_SKEW_STEPS..=TOTP_SKEW_STEPS)
+        .map(|offset| current_step + offset)
+        .find(|step| generate_code(secret, *step).as_deref() == Some(code))
+}
+
+/// Base32编码（RFC 4648，不带填充）
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            output.push(BASE32_ALPHABET[((buffer >> (bits - 5)) & 0x1f) as usize] as char);
+            bits -= 5;
+        }
+    }
+    if bits > 0 {
+        output.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    output
+}
+
+/// Base32解码，忽略大小写、空格和填充字符
+fn base32_decode(input: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for c in input.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let value = BASE32_ALPHABET.iter().position(|&a| a as char == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            output.push(((buffer >> (bits - 8)) & 0xff) as u8);
+            bits -= 8;
+        }
+    }
+
+    Some(output)
+}
+
+/// 对URI中的标签和参数进行百分号编码
+fn percent_encode(input: &str) -> String {
+    input.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 6238 附录B使用的SHA-1密钥 "12345678901234567890" 的Base32编码
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn rfc6238_sha1_vectors() {
+        // 附录B给出的是8位验证码，6位验证码为其后6位
+        let vectors = [
+            (59, "94287082"),
+            (1111111109, "07081804"),
+            (1111111111, "14050471"),
+            (1234567890, "89005924"),
+            (2000000000, "69279037"),
+            (20000000000, "65353130"),
+        ];
+
+        for (unix_seconds, expected) in vectors {
+            let code = generate_code(RFC_SECRET, time_step(unix_seconds)).unwrap();
+            assert_eq!(code, expected[2..], "unix time {}", unix_seconds);
+            assert_eq!(verify_code(RFC_SECRET, &expected[2..], unix_seconds), Some(time_step(unix_seconds)));
+        }
+    }
+
+    #[test]
+    fn verify_code_accepts_adjacent_steps_only() {
+        let unix_seconds = 1234567890;
+        let previous = generate_code(RFC_SECRET, time_step(unix_seconds) - 1).unwrap();
+        let distant = generate_code(RFC_SECRET, time_step(unix_seconds) - 2).unwrap();
+
+        assert_eq!(verify_code(RFC_SECRET, &previous, unix_seconds), Some(time_step(unix_seconds) - 1));
+        assert_eq!(verify_code(RFC_SECRET, &distant, unix_seconds), None);
+        assert_eq!(verify_code(RFC_SECRET, "12345", unix_seconds), None);
+        assert_eq!(verify_code(RFC_SECRET, "abcdef", unix_seconds), None);
+    }
+
+    #[test]
+    fn base32_rfc4648_vectors() {
+        assert_eq!(base32_encode(b"12345678901234567890"), RFC_SECRET);
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_decode("MZXW6YTBOI======").unwrap(), b"foobar");
+        assert_eq!(base32_decode("mzxw 6ytb oi").unwrap(), b"foobar");
+        assert_eq!(base32_decode("MZXW1"), None);
+    }
+
+    #[test]
+    fn base32_round_trip() {
+        for len in 0..=40 {
+            let bytes: Vec<u8> = (0..len).map(|i| (i * 37 + 11) as u8).collect();
+            assert_eq!(base32_decode(&base32_encode(&bytes)).unwrap(), bytes);
+        }
+
+        let secret = generate_secret();
+        assert_eq!(secret.len(), 32);
+        assert_eq!(base32_decode(&secret).unwrap().len(), 20);
+    }
+}